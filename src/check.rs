@@ -0,0 +1,62 @@
+//! Health checks of tagged paths for `rtag check`.
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::fingerprint::Fingerprint;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Problem {
+    /// The path doesn't exist anymore.
+    Missing,
+    /// The path was replaced by a symlink. Tagged paths are canonical, so
+    /// they never are symlinks when they are tagged.
+    Symlink,
+    /// The size or modification time differs from the recorded fingerprint.
+    Modified,
+}
+
+impl Problem {
+    /// Returns `true` for problems that leave the item without its file.
+    pub fn is_dangling(self) -> bool {
+        self != Problem::Modified
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Problem::Missing => "missing",
+            Problem::Symlink => "symlink",
+            Problem::Modified => "modified",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub path: String,
+    pub problem: Problem,
+}
+
+/// Checks the file of an item at `path` against its recorded fingerprint.
+/// Items without a fingerprint are only checked for existence.
+pub fn check_path(path: &Path, recorded: Option<&Fingerprint>) -> Option<Problem> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Some(Problem::Missing),
+    };
+    if metadata.file_type().is_symlink() {
+        return Some(Problem::Symlink);
+    }
+    let current = Fingerprint::of(path, false).ok()?;
+    match recorded {
+        Some(recorded) if metadata.is_file() && (recorded.size != current.size || recorded.mtime != current.mtime) => {
+            Some(Problem::Modified)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,101 @@
+//! File fingerprints used by `rtag repair` to find items that were moved or
+//! renamed outside of rtag.
+//!
+//! A moved file usually keeps its device and inode. When it was copied to
+//! another file system instead, it is matched by size and modification time,
+//! or by content hash if one was recorded when it was tagged.
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub dev: i64,
+    pub inode: i64,
+    pub size: i64,
+    /// Modification time in seconds since the epoch.
+    pub mtime: i64,
+    pub content_hash: Option<String>,
+}
+
+impl Fingerprint {
+    /// Reads the fingerprint of `path` without following symlinks. The
+    /// content hash is only computed for regular files and with `hash`.
+    pub fn of(path: &Path, hash: bool) -> io::Result<Fingerprint> {
+        let metadata = fs::symlink_metadata(path)?;
+        let content_hash = if hash && metadata.is_file() { Some(content_hash(path)?) } else { None };
+        Ok(Fingerprint {
+            dev: metadata.dev() as i64,
+            inode: metadata.ino() as i64,
+            size: metadata.size() as i64,
+            mtime: metadata.mtime(),
+            content_hash,
+        })
+    }
+
+    fn same_inode(&self, other: &Fingerprint) -> bool {
+        self.dev == other.dev && self.inode == other.inode
+    }
+
+    fn same_size_and_mtime(&self, other: &Fingerprint) -> bool {
+        self.size == other.size && self.mtime == other.mtime
+    }
+}
+
+/// Returns the hex encoded SHA-256 of the contents of a file.
+pub fn content_hash(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Match {
+    /// The item was moved to this path.
+    Certain(PathBuf),
+    /// The item may have been moved to one of these paths.
+    Ambiguous(Vec<PathBuf>),
+}
+
+/// Looks for the new location of a missing item among `candidates`.
+///
+/// A candidate with the same inode, size and modification time, or the only
+/// candidate with the recorded content hash, is a certain match. Candidates
+/// with the same inode but changed contents, or with the same size and
+/// modification time but no recorded hash to compare, are ambiguous.
+/// `hash_of` is only called for candidates of the item's size.
+pub fn find_moved<F>(item: &Fingerprint, candidates: &[(PathBuf, Fingerprint)], mut hash_of: F) -> Option<Match>
+where
+    F: FnMut(&Path) -> Option<String>,
+{
+    let same_content = |path: &Path, candidate: &Fingerprint, hash_of: &mut F| match &item.content_hash {
+        Some(hash) => candidate.size == item.size && hash_of(path).as_ref() == Some(hash),
+        None => candidate.same_size_and_mtime(item),
+    };
+
+    let mut possible = Vec::new();
+    let mut certain = Vec::new();
+    for (path, candidate) in candidates {
+        let same_inode = candidate.same_inode(item);
+        let same_content = same_content(path, candidate, &mut hash_of);
+        if same_inode && same_content {
+            return Some(Match::Certain(path.clone()));
+        }
+        if same_content && item.content_hash.is_some() {
+            certain.push(path.clone());
+        }
+        if same_inode || same_content {
+            possible.push(path.clone());
+        }
+    }
+    if certain.len() == 1 {
+        return Some(Match::Certain(certain.remove(0)));
+    }
+    if possible.is_empty() {
+        None
+    } else {
+        Some(Match::Ambiguous(possible))
+    }
+}
@@ -0,0 +1,190 @@
+//! Append-only history of the operations that changed the store, and undo.
+//!
+//! Functions that change tags or items run as an operation, see
+//! [`in_operation`]. While an operation runs, triggers record every row it
+//! inserts, updates or deletes, with the old values of what it changed.
+//! Undoing an operation reverts these changes in reverse order, and is itself
+//! recorded as an `undo` operation, so the history is never rewritten.
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension, Result, NO_PARAMS};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::HashMap;
+
+use crate::rtag_sqlite::in_savepoint;
+
+/// Name of the operations that undo other operations.
+pub const UNDO: &str = "undo";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub id: i64,
+    pub operation: String,
+    /// The arguments as a JSON object.
+    pub arguments: String,
+    pub time: String,
+    /// The number of rows inserted, updated or deleted.
+    pub affected_rows: i64,
+    /// The id of the operation that undid this one.
+    pub undone_by: Option<i64>,
+}
+
+fn current_operation(conn: &Connection) -> Result<Option<i64>> {
+    conn.prepare_cached("SELECT history_id FROM history_current")?
+        .query_row(NO_PARAMS, |row| row.get(0))
+        .optional()
+}
+
+/// Runs `f` as the operation `operation` with `arguments`, in a savepoint.
+/// When called while another operation runs, `f` becomes part of it, so e.g.
+/// the tags created while tagging are undone with the tagging. Operations
+/// that change nothing are not recorded.
+pub fn in_operation<T, F>(conn: &Connection, operation: &str, arguments: JsonValue, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    if current_operation(conn)?.is_some() {
+        return in_savepoint(conn, f);
+    }
+    conn.execute_batch("SAVEPOINT rtag_operation")?;
+    let result = (|| {
+        conn.prepare_cached("INSERT INTO history (operation, arguments) VALUES (?1, ?2)")?
+            .execute(params![operation, arguments.to_string()])?;
+        let id = conn.last_insert_rowid();
+        conn.prepare_cached("INSERT INTO history_current (history_id) VALUES (?1)")?
+            .execute(params![id])?;
+        let value = f()?;
+        conn.prepare_cached("DELETE FROM history_current")?.execute(NO_PARAMS)?;
+        let changed: bool = conn
+            .prepare_cached("SELECT EXISTS (SELECT 1 FROM history_changes WHERE history_id = ?1)")?
+            .query_row(params![id], |row| row.get(0))?;
+        Ok((value, changed))
+    })();
+    match result {
+        Ok((value, true)) => {
+            conn.execute_batch("RELEASE rtag_operation")?;
+            Ok(value)
+        }
+        Ok((value, false)) => {
+            conn.execute_batch("ROLLBACK TO rtag_operation; RELEASE rtag_operation")?;
+            Ok(value)
+        }
+        Err(error) => {
+            conn.execute_batch("ROLLBACK TO rtag_operation; RELEASE rtag_operation")?;
+            Err(error)
+        }
+    }
+}
+
+/// Returns which operation undid which, from the arguments of the `undo`
+/// operations.
+fn undone_by(conn: &Connection) -> Result<HashMap<i64, i64>> {
+    let mut stmt = conn.prepare_cached("SELECT id, arguments FROM history WHERE operation = ?1")?;
+    let rows = stmt.query_map(params![UNDO], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+    let mut undone = HashMap::new();
+    for row in rows {
+        let (undo_id, arguments) = row?;
+        let arguments: JsonValue = serde_json::from_str(&arguments).unwrap_or_default();
+        for id in arguments["operations"].as_array().into_iter().flatten().filter_map(JsonValue::as_i64) {
+            undone.insert(id, undo_id);
+        }
+    }
+    Ok(undone)
+}
+
+/// Returns the operations, most recent first. Returns the `limit` most
+/// recent ones if given.
+pub fn get_history(conn: &Connection, limit: Option<usize>) -> Result<Vec<Operation>> {
+    let undone = undone_by(conn)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT history.id, operation, arguments, time, count(history_changes.id) FROM history \
+         LEFT JOIN history_changes ON history_changes.history_id = history.id \
+         GROUP BY history.id ORDER BY history.id DESC LIMIT ?1",
+    )?;
+    let limit = limit.map_or(-1, |limit| limit as i64);
+    let operations = stmt.query_map(params![limit], |row| {
+        let id = row.get(0)?;
+        Ok(Operation {
+            id,
+            operation: row.get(1)?,
+            arguments: row.get(2)?,
+            time: row.get(3)?,
+            affected_rows: row.get(4)?,
+            undone_by: undone.get(&id).copied(),
+        })
+    })?;
+    operations.collect()
+}
+
+fn sql_value(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(value) => Value::Integer(*value as i64),
+        JsonValue::Number(number) => match number.as_i64() {
+            Some(int) => Value::Integer(int),
+            None => Value::Real(number.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(text) => Value::Text(text.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+/// Reverts one recorded change of a row.
+fn revert_change(conn: &Connection, table: &str, action: &str, row_id: i64, old_row: Option<String>) -> Result<()> {
+    let old_row: Map<String, JsonValue> = old_row
+        .and_then(|old_row| serde_json::from_str(&old_row).ok())
+        .unwrap_or_default();
+    let columns: Vec<&String> = old_row.keys().collect();
+    let mut values: Vec<Value> = old_row.values().map(sql_value).collect();
+    let sql = match action {
+        "insert" => {
+            values = vec![Value::Integer(row_id)];
+            format!("DELETE FROM {} WHERE rowid = ?", table)
+        }
+        "update" if columns.is_empty() => return Ok(()),
+        "update" => {
+            values.push(Value::Integer(row_id));
+            let assignments: Vec<String> = columns.iter().map(|column| format!("{} = ?", column)).collect();
+            format!("UPDATE {} SET {} WHERE rowid = ?", table, assignments.join(", "))
+        }
+        _ => {
+            let columns: Vec<&str> = columns.iter().map(|column| column.as_str()).collect();
+            let placeholders = vec!["?"; columns.len()];
+            format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(", "), placeholders.join(", "))
+        }
+    };
+    conn.prepare_cached(&sql)?.execute(&values)?;
+    Ok(())
+}
+
+/// Undoes the `count` most recent operations that are not undone yet, and
+/// returns them. Undo operations themselves are not undone. Fails without
+/// changes if a later change conflicts, e.g. when a restored tag was created
+/// again in the meantime.
+pub fn undo(conn: &Connection, count: usize) -> Result<Vec<Operation>> {
+    let operations: Vec<Operation> = get_history(conn, None)?
+        .into_iter()
+        .filter(|operation| operation.operation != UNDO && operation.undone_by.is_none())
+        .take(count)
+        .collect();
+    if operations.is_empty() {
+        return Ok(operations);
+    }
+    let ids: Vec<i64> = operations.iter().map(|operation| operation.id).collect();
+    in_operation(conn, UNDO, json!({ "operations": ids }), || {
+        // a row may be restored before the rows it references
+        conn.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT table_name, action, row_id, old_row FROM history_changes WHERE history_id = ?1 ORDER BY id DESC",
+        )?;
+        for id in &ids {
+            let changes = stmt
+                .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+                .collect::<Result<Vec<(String, String, i64, Option<String>)>>>()?;
+            for (table, action, row_id, old_row) in changes {
+                revert_change(conn, &table, &action, row_id, old_row)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(operations)
+}
@@ -0,0 +1,190 @@
+//! Registry of the kinds of items that can be tagged.
+//!
+//! Every item has a kind, stored in `items.type`. A kind decides which command
+//! line arguments denote its items, how they are resolved into the form stored
+//! in the database, whether an item still exists and how it is displayed.
+//! Paths, URLs and free-text notes are built in; further kinds implement
+//! [`ItemKind`] and are added with [`Registry::register`].
+use glob::MatchOptions;
+use std::fs;
+
+use crate::location::DbLocation;
+use crate::rtag_sqlite::{NOTE_ITEM, PATH_ITEM, URL_ITEM};
+use crate::urls::{is_url, normalize_url, FragmentPolicy};
+use crate::walk::{walk_dir, WalkOptions};
+
+/// Options of the command that resolves the arguments.
+pub struct Context<'a> {
+    pub db: &'a DbLocation,
+    /// Set to tag the contents of directories instead of the directories.
+    pub walk_options: Option<&'a WalkOptions>,
+    pub fragment_policy: FragmentPolicy,
+}
+
+pub trait ItemKind {
+    /// The name stored in `items.type`.
+    fn name(&self) -> &'static str;
+
+    /// Returns `true` if a command line argument denotes items of this kind.
+    fn accepts(&self, arg: &str) -> bool;
+
+    /// Resolves an argument into the stored form of one or more items.
+    fn resolve(&self, arg: &str, context: &Context) -> Result<Vec<String>, String>;
+
+    /// Returns whether an item still exists, `None` if that can't be checked.
+    fn exists(&self, stored: &str, db: &DbLocation) -> Option<bool>;
+
+    /// Returns the text shown for an item.
+    fn display(&self, stored: &str) -> String {
+        String::from(stored)
+    }
+}
+
+/// Files and directories. Paths are canonical and, in a project-local
+/// database, relative to the project root.
+pub struct PathKind;
+
+impl ItemKind for PathKind {
+    fn name(&self) -> &'static str {
+        PATH_ITEM
+    }
+
+    /// Accepts every argument, so it is tried last.
+    fn accepts(&self, _arg: &str) -> bool {
+        true
+    }
+
+    /// Arguments that don't exist but contain glob characters are expanded,
+    /// directories are replaced by their contents if walk options are given.
+    fn resolve(&self, arg: &str, context: &Context) -> Result<Vec<String>, String> {
+        if let Ok(path) = fs::canonicalize(arg) {
+            return Ok(match context.walk_options {
+                Some(options) if path.is_dir() => {
+                    walk_dir(&path, options).iter().map(|entry| context.db.to_stored_path(entry)).collect()
+                }
+                _ => vec![context.db.to_stored_path(&path)],
+            });
+        }
+        if !arg.contains(['*', '?', '[']) {
+            return Err(format!("Couldn't find the path {}", arg));
+        }
+        // like the shell, `*` doesn't match hidden files
+        let options = MatchOptions { require_literal_leading_dot: true, ..MatchOptions::new() };
+        let entries = glob::glob_with(arg, options).map_err(|error| format!("Invalid pattern {}: {}", arg, error))?;
+        let mut paths = Vec::new();
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            match fs::canonicalize(&entry) {
+                Ok(path) => paths.push(context.db.to_stored_path(&path)),
+                Err(error) => return Err(format!("Couldn't find the path {}: {}", entry.display(), error)),
+            }
+        }
+        if paths.is_empty() {
+            return Err(format!("No paths match {}", arg));
+        }
+        Ok(paths)
+    }
+
+    fn exists(&self, stored: &str, db: &DbLocation) -> Option<bool> {
+        Some(fs::symlink_metadata(db.to_absolute_path(stored)).is_ok())
+    }
+}
+
+/// Web pages and other resources with a URL, see [`crate::urls`].
+pub struct UrlKind;
+
+impl ItemKind for UrlKind {
+    fn name(&self) -> &'static str {
+        URL_ITEM
+    }
+
+    fn accepts(&self, arg: &str) -> bool {
+        is_url(arg)
+    }
+
+    fn resolve(&self, arg: &str, context: &Context) -> Result<Vec<String>, String> {
+        normalize_url(arg, context.fragment_policy)
+            .map(|url| vec![url])
+            .map_err(|error| format!("Invalid URL {}: {}", arg, error))
+    }
+
+    /// URLs are not fetched, so their existence is unknown.
+    fn exists(&self, _stored: &str, _db: &DbLocation) -> Option<bool> {
+        None
+    }
+}
+
+/// Free text written as `note:<text>`, e.g. `note:call the landlord`.
+pub struct NoteKind;
+
+/// Prefix of note items, also used for searching notes, see [`crate::query`].
+pub const NOTE_PREFIX: &str = "note:";
+
+impl ItemKind for NoteKind {
+    fn name(&self) -> &'static str {
+        NOTE_ITEM
+    }
+
+    fn accepts(&self, arg: &str) -> bool {
+        arg.starts_with(NOTE_PREFIX)
+    }
+
+    /// Stores the note with its prefix, so it can't clash with a relative
+    /// path of the same text.
+    fn resolve(&self, arg: &str, _context: &Context) -> Result<Vec<String>, String> {
+        let text = arg[NOTE_PREFIX.len()..].trim();
+        if text.is_empty() {
+            return Err(String::from("A note must not be empty"));
+        }
+        Ok(vec![format!("{}{}", NOTE_PREFIX, text)])
+    }
+
+    fn exists(&self, _stored: &str, _db: &DbLocation) -> Option<bool> {
+        Some(true)
+    }
+
+    /// Shows the text in quotes, so it can't be mistaken for a path.
+    fn display(&self, stored: &str) -> String {
+        format!("\"{}\"", stored.strip_prefix(NOTE_PREFIX).unwrap_or(stored))
+    }
+}
+
+/// The known item kinds. Arguments are matched against the kinds in order.
+pub struct Registry {
+    kinds: Vec<Box<dyn ItemKind>>,
+}
+
+impl Default for Registry {
+    /// Returns a registry with the built-in kinds.
+    fn default() -> Self {
+        Registry {
+            kinds: vec![Box::new(UrlKind), Box::new(NoteKind), Box::new(PathKind)],
+        }
+    }
+}
+
+impl Registry {
+    /// Adds a kind. It is tried before the kinds registered earlier, so it can
+    /// claim arguments that would otherwise be taken as paths.
+    pub fn register(&mut self, kind: Box<dyn ItemKind>) {
+        self.kinds.insert(0, kind);
+    }
+
+    /// Returns the kind of a command line argument.
+    pub fn kind_of(&self, arg: &str) -> Option<&dyn ItemKind> {
+        self.kinds.iter().map(|kind| kind.as_ref()).find(|kind| kind.accepts(arg))
+    }
+
+    /// Returns the kind stored as `name` in `items.type`.
+    pub fn get(&self, name: &str) -> Option<&dyn ItemKind> {
+        self.kinds.iter().map(|kind| kind.as_ref()).find(|kind| kind.name() == name)
+    }
+
+    /// Returns the text shown for an item of type `item_type`. Items of an
+    /// unknown type are shown as stored.
+    pub fn display(&self, item_type: &str, stored: &str) -> String {
+        match self.get(item_type) {
+            Some(kind) => kind.display(stored),
+            None => String::from(stored),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+extern crate prettytable;
+pub mod check;
+pub mod fingerprint;
+pub mod history;
+pub mod kinds;
+pub mod location;
+pub mod migrations;
+pub mod output;
+pub mod query;
+pub mod rtag_sqlite;
+pub mod times;
+pub mod urls;
+pub mod values;
+pub mod walk;
+pub mod watch;
@@ -0,0 +1,103 @@
+//! Resolution of the database file shared by all subcommands.
+//!
+//! The database is looked up in this order:
+//!
+//! 1. the `--db <path>` command line option,
+//! 2. the `RTAG_DB` environment variable,
+//! 3. a project-local `.rtag/rtag.db`, found by walking up from the current
+//!    directory the way git finds `.git`,
+//! 4. `$XDG_DATA_HOME/rtag/rtag.db`, falling back to `~/.local/share/rtag/rtag.db`.
+//!
+//! Paths in a project-local database are stored relative to its root, i.e. the
+//! directory containing `.rtag/`, so the project can be moved or cloned.
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const DB_ENV_VAR: &str = "RTAG_DB";
+pub const LOCAL_DIR_NAME: &str = ".rtag";
+const DB_FILE_NAME: &str = "rtag.db";
+
+/// The database a command operates on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbLocation {
+    pub path: PathBuf,
+    /// Root directory of a project-local database, `None` for the global one.
+    pub root: Option<PathBuf>,
+}
+
+impl DbLocation {
+    /// Creates a location for a database file. A file inside a `.rtag`
+    /// directory is treated as project-local with the parent as its root.
+    pub fn from_db_path(path: PathBuf) -> Self {
+        let root = path
+            .parent()
+            .filter(|dir| dir.file_name() == Some(OsStr::new(LOCAL_DIR_NAME)))
+            .and_then(Path::parent)
+            .map(|root| fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()));
+        DbLocation { path, root }
+    }
+
+    /// Converts a canonical path into the form stored in the database:
+    /// relative to the root for local databases, absolute otherwise.
+    pub fn to_stored_path(&self, path: &Path) -> String {
+        match self.root.as_ref().and_then(|root| path.strip_prefix(root).ok()) {
+            Some(relative) if relative.as_os_str().is_empty() => String::from("."),
+            Some(relative) => relative.to_string_lossy().into_owned(),
+            None => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Converts a stored path back into an absolute path.
+    pub fn to_absolute_path(&self, stored: &str) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(stored),
+            None => PathBuf::from(stored),
+        }
+    }
+}
+
+/// Walks up from `start` and returns the first project-local database found.
+pub fn find_local_db(start: &Path) -> Option<DbLocation> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(LOCAL_DIR_NAME).join(DB_FILE_NAME))
+        .find(|db| db.is_file())
+        .map(DbLocation::from_db_path)
+}
+
+/// Creates the `.rtag` directory in `dir` and returns the location of its
+/// database. The database file itself is created when it is first opened.
+pub fn init_local_db(dir: &Path) -> io::Result<DbLocation> {
+    let local_dir = fs::canonicalize(dir)?.join(LOCAL_DIR_NAME);
+    fs::create_dir_all(&local_dir)?;
+    Ok(DbLocation::from_db_path(local_dir.join(DB_FILE_NAME)))
+}
+
+/// Returns the default database path in the XDG data directory.
+pub fn default_db_path() -> Option<PathBuf> {
+    data_dir(env::var_os("XDG_DATA_HOME"), env::var_os("HOME")).map(|dir| dir.join("rtag").join(DB_FILE_NAME))
+}
+
+/// Returns `$XDG_DATA_HOME`, or `$HOME/.local/share` if it is unset or not
+/// absolute, as demanded by the XDG base directory specification.
+pub fn data_dir(xdg_data_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    xdg_data_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.filter(|home| !home.is_empty()).map(|home| PathBuf::from(home).join(".local").join("share")))
+}
+
+/// Resolves the database from the `--db` option, the environment, a local
+/// database above `cwd` and the default location. Returns `None` if no
+/// location could be determined.
+pub fn resolve_db_location(cli_db: Option<&Path>, cwd: &Path) -> Option<DbLocation> {
+    cli_db
+        .map(PathBuf::from)
+        .or_else(|| env::var_os(DB_ENV_VAR).filter(|db| !db.is_empty()).map(PathBuf::from))
+        .map(DbLocation::from_db_path)
+        .or_else(|| find_local_db(cwd))
+        .or_else(|| default_db_path().map(DbLocation::from_db_path))
+}
@@ -1,43 +1,152 @@
-#[macro_use] extern crate prettytable;
 extern crate clap;
-pub mod rtag_sqlite;
 
 use rusqlite::Connection;
+use serde_json::json;
 
-use crate::rtag_sqlite::{create_db_and_initialize_tables, create_new_tag, insert_path, show_all, show_tags, show_paths, delete_by_id, delete_by_tag};
-use clap::{App, AppSettings, Arg, SubCommand};
+use rtag::check::{check_path, Finding, Problem};
+use rtag::fingerprint::{content_hash, find_moved, Fingerprint, Match};
+use rtag::history::{get_history, in_operation, undo};
+use rtag::kinds::{Context, Registry};
+use rtag::location::{self, DbLocation};
+use rtag::output::{to_stdout, write_nul_delimited, write_records, Format, FORMAT_NAMES};
+use rtag::times::{parse_time, TimeRange};
+use rtag::urls::FragmentPolicy;
+use rtag::walk::{walk_dir, EntryType, WalkOptions};
+use rtag::watch::{apply_change, changes_of, default_roots, Change};
+use rtag::{migrations, query};
+use rtag::rtag_sqlite::{
+    add_alias, create_db_and_initialize_tables, create_new_tag, delete_by_id, forget_path, get_aliases,
+    get_descendant_ids, get_ids_of_tags, get_item_note, get_item_paths, get_items, get_path_items,
+    get_tag_description, get_tag_usage, insert_items, merge_tags, move_item, open_db, remove_alias, rename_tag, search_tagged,
+    set_fingerprint, set_item_note, set_tag_description, show_all, show_paths, show_tag_tree, show_tags, untag_path,
+    get_tags_of_path, ShowOptions, ITEM_FIELDS, SHOW_SORT_NAMES, TAG_SORT_NAMES, PATH_ITEM, SHOW_FIELDS,
+};
+use chrono::Utc;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use notify::{RecursiveMode, Watcher};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
 use std::fs;
-use std::path::Path;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
 
 fn main() {
-    let conn = create_db_and_initialize_tables().unwrap();
-
+    let columns_help = format!("Comma-separated columns to show, of {}", SHOW_FIELDS.join(", "));
+    let format_help = format!(
+        "Output format, one of {} or a template like '{{path}}\\t{{tags|join(\",\")}}' that writes a line per row",
+        FORMAT_NAMES.join(", ")
+    );
+    let tags_format_help = format!("Output format, one of {} or a template of {}", FORMAT_NAMES.join(", "), TAG_FIELDS.join(", "));
+    let log_format_help = format!("Output format, one of {} or a template of {}", FORMAT_NAMES.join(", "), LOG_FIELDS.join(", "));
     let matches = App::new("rtag")
         .about("Revolutional tagging")
         .version("1.0")
         .author("Me")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("db")
+                .long("db")
+                .takes_value(true)
+                .global(true)
+                .help("Database file to use. Defaults to $RTAG_DB, then $XDG_DATA_HOME/rtag/rtag.db"),
+        )
         .subcommand(
             // todo: must take two arguments!!!
             SubCommand::with_name("tag")
                 .about("tag files")
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .setting(AppSettings::ArgsNegateSubcommands)
                 .arg(
                     Arg::with_name("tag")
-                        .help("Tag to use for the path")
+                        .help("Comma separated tags to use for the paths, e.g. a,b,c. Tags can carry a value, e.g. rating=4,due=2026-11-01")
                         .required(true),
                 )
                 .arg(
                     Arg::with_name("path")
-                        .help("The path to tag")
-                        .required(true),
+                        .help("The paths, URLs or note:<text> notes to tag. Quoted glob patterns like 'src/**/*.rs' are expanded")
+                        .required_unless("stdin0")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("stdin0")
+                        .long("stdin0")
+                        .help("Read NUL separated paths from stdin, e.g. from find -print0"),
+                )
+                .arg(
+                    Arg::with_name("recursive")
+                        .long("recursive")
+                        .short("r")
+                        .help("Tag the contents of directories, skipping files ignored by .gitignore or .rtagignore"),
+                )
+                .arg(
+                    Arg::with_name("hidden")
+                        .long("hidden")
+                        .requires("recursive")
+                        .help("Also tag hidden files when tagging recursively"),
+                )
+                .arg(
+                    Arg::with_name("max-depth")
+                        .long("max-depth")
+                        .takes_value(true)
+                        .requires("recursive")
+                        .help("Descend at most this many directories when tagging recursively"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .takes_value(true)
+                        .possible_values(&["f", "d"])
+                        .requires("recursive")
+                        .help("Tag files (f, the default) or directories (d) when tagging recursively"),
+                )
+                .arg(
+                    Arg::with_name("strip-fragment")
+                        .long("strip-fragment")
+                        .help("Drop the #fragment of URLs, so all sections of a page are one item"),
+                )
+                .arg(
+                    Arg::with_name("hash")
+                        .long("hash")
+                        .help("Record a hash of the file contents, so 'rtag repair' can find copies of moved files"),
+                )
+                .subcommand(
+                    SubCommand::with_name("rename").about("rename a tag")
+                    .arg(Arg::with_name("old").required(true))
+                    .arg(Arg::with_name("new").required(true))
+                    .arg(
+                        Arg::with_name("cascade")
+                        .long("cascade")
+                        .short("r")
+                        .help("Also rename all child tags without asking"))
+                )
+                .subcommand(
+                    SubCommand::with_name("merge").about("merge tags: merge <src>... into <dst>")
+                    .arg(
+                        Arg::with_name("tags")
+                        .help("Tags to merge, followed by 'into' and the destination tag")
+                        .required(true)
+                        .multiple(true))
                 ),
         )
         .subcommand(
             SubCommand::with_name("search").about("search in tags").arg(
                 Arg::with_name("pattern")
-                    .help("The remote repo to push things to")
-                    .required(true),
-            ),
+                    .help("Tag query, e.g. 'rust AND (paper OR draft) AND NOT archived' or 'rating>=4 AND due<today'")
+                    .required(true)
+                    .multiple(true),
+            )
+            .arg(format_arg().help("Output format, by default one path per line"))
+            .arg(nul_arg())
+            .args(&time_range_args())
+            .arg(
+                Arg::with_name("sort")
+                    .long("sort")
+                    .takes_value(true)
+                    .possible_values(&["path", "tagged_at"])
+                    .default_value("path")
+                    .help("Sort by path, or by the time the items were last tagged with the most recent first")),
         )
         .subcommand(
             SubCommand::with_name("create").about("create new tag").arg(
@@ -69,7 +178,64 @@ fn main() {
                         .multiple(true)
                         .conflicts_with("all")
                         .conflicts_with("tags"))
-            )  
+                .arg(
+                    Arg::with_name("long")
+                        .long("long")
+                        .short("l")
+                        .help("Also show the notes of the items and the descriptions of the tags"))
+                .arg(
+                    Arg::with_name("columns")
+                        .long("columns")
+                        .takes_value(true)
+                        .help(&columns_help))
+                .arg(
+                    Arg::with_name("by-item")
+                        .long("by-item")
+                        .help("Show one row per item with all its tags"))
+                .arg(format_arg().default_value("table").help(&format_help))
+                .arg(nul_arg())
+                .args(&time_range_args())
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .possible_values(SHOW_SORT_NAMES)
+                        .help("Sort by path, by tag, or by the time of tagging with the most recent first"))
+            )
+        .subcommand(
+            SubCommand::with_name("log").about("show the history of changes, most recent first")
+            .arg(
+                Arg::with_name("limit")
+                .long("limit")
+                .short("n")
+                .takes_value(true)
+                .validator(|count| count.parse::<usize>().map(|_| ()).map_err(|error| error.to_string()))
+                .help("Only show this many operations"))
+            .arg(format_arg().default_value("table").help(&log_format_help))
+        )
+        .subcommand(
+            SubCommand::with_name("undo").about("undo the most recent changes")
+            .arg(
+                Arg::with_name("count")
+                .default_value("1")
+                .validator(|count| count.parse::<usize>().map(|_| ()).map_err(|error| error.to_string()))
+                .help("Number of operations to undo"))
+        )
+        .subcommand(
+            SubCommand::with_name("tags-of").about("list the tags of paths")
+            .arg(Arg::with_name("paths").help("Tagged paths or URLs").required(true).multiple(true))
+            .arg(format_arg().help("Output format, by default the tags of each path one per line"))
+        )
+        .subcommand(
+            SubCommand::with_name("note").about("show or set the note of a tagged path")
+            .arg(Arg::with_name("path").help("The tagged path or URL").required(true))
+            .arg(Arg::with_name("text").help("The new note, an empty text removes it"))
+        )
+        .subcommand(
+            SubCommand::with_name("describe").about("show or set the description of a tag")
+            .arg(Arg::with_name("tag").help("The tag").required(true))
+            .arg(Arg::with_name("text").help("The new description, an empty text removes it"))
+        )
         .subcommand(
             SubCommand::with_name("delete").about("delete existing tags")
             .arg(
@@ -85,100 +251,410 @@ fn main() {
                 .takes_value(true)
                 .multiple(true)
             )
+            .arg(
+                Arg::with_name("cascade")
+                .long("cascade")
+                .short("r")
+                .help("Also delete all child tags without asking")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("untag").about("remove a tag from paths, keeping the tag")
+            .arg(Arg::with_name("tag").help("Tag to remove").required(true))
+            .arg(Arg::with_name("path").help("Paths to untag").required(true).multiple(true))
+        )
+        .subcommand(
+            SubCommand::with_name("forget").about("remove paths from all of their tags, keeping the tags")
+            .arg(Arg::with_name("path").help("Paths to forget").required(true).multiple(true))
+        )
+        .subcommand(
+            SubCommand::with_name("repair").about("find moved or renamed files and update their paths")
+            .arg(
+                Arg::with_name("root")
+                .help("Directories to search for the missing files")
+                .required(true)
+                .multiple(true))
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+            .about("report tagged paths that are missing, became symlinks or were modified since tagging. \
+                    Exits with 1 if missing paths or symlinks remain")
+            .arg(
+                Arg::with_name("prune")
+                .long("prune")
+                .help("Forget missing paths"))
+            .arg(
+                Arg::with_name("json")
+                .long("json")
+                .help("Print the findings as JSON"))
+        )
+        .subcommand(
+            SubCommand::with_name("watch").about("follow renames and deletes of tagged files as they happen")
+            .arg(
+                Arg::with_name("root")
+                .help("Directories to watch. Defaults to the project root of a local database, \
+                       otherwise to the directories of all tagged paths")
+                .multiple(true))
+        )
+        .subcommand(
+            SubCommand::with_name("tags").about("list tags with the number of their items")
+            .arg(
+                Arg::with_name("tree")
+                .long("tree")
+                .conflicts_with_all(&["sort", "min-count", "unused", "format"])
+                .help("Show the tag hierarchy as a tree"))
+            .arg(
+                Arg::with_name("sort")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(TAG_SORT_NAMES)
+                .default_value("name")
+                .help("Sort by name, or by count, creation or last use with the highest or newest first"))
+            .arg(
+                Arg::with_name("min-count")
+                .long("min-count")
+                .takes_value(true)
+                .validator(|count| count.parse::<i64>().map(|_| ()).map_err(|error| error.to_string()))
+                .help("Only list tags with at least this many items"))
+            .arg(
+                Arg::with_name("unused")
+                .long("unused")
+                .conflicts_with("min-count")
+                .help("Only list tags without items"))
+            .arg(format_arg().default_value("table").help(&tags_format_help))
+        )
+        .subcommand(
+            SubCommand::with_name("alias").about("manage tag aliases")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("add").about("make an alias resolve to a tag")
+                .arg(Arg::with_name("alias").help("The alias, e.g. ml").required(true))
+                .arg(Arg::with_name("tag").help("The canonical tag, e.g. machine-learning").required(true))
+            )
+            .subcommand(
+                SubCommand::with_name("rm").about("remove aliases")
+                .arg(Arg::with_name("alias").required(true).multiple(true))
+            )
+            .subcommand(SubCommand::with_name("list").about("list all aliases"))
+        )
+        .subcommand(
+            SubCommand::with_name("init").about("create a project-local database in .rtag/")
+            .arg(
+                Arg::with_name("dir")
+                .help("Directory to initialize, defaults to the current directory"))
+        )
+        .subcommand(
+            SubCommand::with_name("db").about("manage the database")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("migrate").about("apply pending schema migrations")
+                .arg(
+                    Arg::with_name("dry-run")
+                    .long("dry-run")
+                    .help("Only list the pending migrations"))
+            )
         )
         .get_matches();
 
-    // At this point, the matches we have point to git. Keep this in mind...
-
-    // You can check if one of git's subcommands was used
-    if matches.is_present("tag") {
-        println!("'tag' was run.");
+    if let ("init", Some(init_matches)) = matches.subcommand() {
+        init_local_db(Path::new(init_matches.value_of("dir").unwrap_or(".")));
+        return;
     }
 
-    // You can see which subcommand was used
-    if let Some(subcommand) = matches.subcommand_name() {
-        println!("'rtag {}' was used", subcommand);
-
-        // It's important to note, this *only* check's git's DIRECT children, **NOT** it's
-        // grandchildren, great grandchildren, etc.
-        //
-        // i.e. if the command `git push remove --stuff foo` was run, the above will only print out,
-        // `git push` was used. We'd need to get push's matches to see further into the tree
-    }
-
-    // An alternative to checking the name is matching on known names. Again notice that only the
-    // direct children are matched here.
-    match matches.subcommand_name() {
-        Some("tag") => println!("'rtag tag' was used"),
-        Some("search") => println!("'rtag search' was used"),
-        Some("create") => println!("'rtag create' was used"),
-        Some("show") => println!("'rtag show' was used"),
-        Some("delete") => println!("'rtag delete' was used"),
-        None => println!("No subcommand was used"),
-        _ => unreachable!(), // Assuming you've listed all direct children above, this is unreachable
+    let db = resolve_db(&matches);
+    if let ("db", Some(db_matches)) = matches.subcommand() {
+        run_db_command(&db.path, db_matches);
+        return;
     }
 
-    // You could get the independent subcommand matches, although this is less common
-    if let Some(clone_matches) = matches.subcommand_matches("tag") {
-        // Now we have a reference to clone's matches
-        println!("Tagging path: {}", clone_matches.value_of("path").unwrap());
-    }
+    let conn = create_db_and_initialize_tables(&db.path).unwrap();
+    let kinds = Registry::default();
 
-    // The most common way to handle subcommands is via a combined approach using
-    // `ArgMatches::subcommand` which returns a tuple of both the name and matches
     match matches.subcommand() {
-        ("tag", Some(clone_matches)) => {
-            // Now we have a reference to clone's matches
-            tag_path(
-                conn,
-                clone_matches.value_of("path"),
-                clone_matches.value_of("tag"),
-            );
-            println!("Tagging {}", clone_matches.value_of("path").unwrap());
-        }
-        ("search", Some(push_matches)) => {
-            // Now we have a reference to push's matches
-            println!(
-                "Searching for {}",
-                push_matches.value_of("pattern").unwrap()
-            );
+        ("tag", Some(tag_matches)) if tag_matches.subcommand_name().is_some() => {
+            run_tag_command(&conn, tag_matches);
+        }
+        ("tag", Some(tag_matches)) => {
+            let tags: Vec<String> = tag_matches.value_of("tag").unwrap().split(',')
+                .filter(|tag| !tag.is_empty()).map(String::from).collect();
+            if tags.is_empty() {
+                eprintln!("No tags given, expected e.g. a,b,c");
+                std::process::exit(1);
+            }
+            let mut args: Vec<String> = tag_matches.values_of("path")
+                .map(|paths| paths.map(String::from).collect()).unwrap_or_default();
+            if tag_matches.is_present("stdin0") {
+                args.extend(read_stdin0());
+            }
+            let walk_options = if tag_matches.is_present("recursive") {
+                Some(walk_options_of(tag_matches))
+            } else {
+                None
+            };
+            let fragment_policy = if tag_matches.is_present("strip-fragment") {
+                FragmentPolicy::Strip
+            } else {
+                FragmentPolicy::Keep
+            };
+            let context = Context { db: &db, walk_options: walk_options.as_ref(), fragment_policy };
+            let items = resolve_items(&kinds, &args, &context);
+            let tx = conn.unchecked_transaction().unwrap();
+            // one operation, so that a single undo removes all kinds of items
+            in_operation(&tx, "tag", json!({ "items": items, "tags": tags }), || {
+                for (item_type, stored) in &items {
+                    insert_items(&tx, stored, item_type, &tags)?;
+                }
+                if let Some(paths) = items.get(PATH_ITEM) {
+                    record_fingerprints(&tx, &db, paths, tag_matches.is_present("hash"));
+                }
+                Ok(())
+            })
+            .unwrap();
+            tx.commit().unwrap();
+            eprintln!("Tagged {} items", items.values().map(Vec::len).sum::<usize>());
+        }
+        ("search", Some(search_matches)) => {
+            let pattern = search_matches.values_of("pattern").unwrap().collect::<Vec<&str>>().join(" ");
+            match query::parse(pattern.as_str()) {
+                Ok(expr) => {
+                    let by_tagged_at = search_matches.value_of("sort") == Some("tagged_at");
+                    let paths = search_tagged(&conn, &expr, &time_range_of(search_matches), by_tagged_at).unwrap();
+                    if search_matches.is_present("nul") {
+                        to_stdout(|out| write_nul_delimited(out, &paths));
+                    } else if let Some(format) = format_of(search_matches, &["path"]) {
+                        let records: Vec<Vec<serde_json::Value>> = paths.into_iter().map(|path| vec![path.into()]).collect();
+                        to_stdout(|out| write_records(out, &format, &["PATH"], &records));
+                    } else {
+                        to_stdout(|out| paths.iter().try_for_each(|path| writeln!(out, "{}", path)));
+                    }
+                }
+                Err(error) => {
+                    eprintln!("Invalid search pattern '{}': {}", pattern, error);
+                    std::process::exit(1);
+                }
+            }
         }
         ("create", Some(create_tag_matches)) => {
-            create_new_tag(&conn, create_tag_matches.value_of("tag").unwrap()).unwrap();
-            println!("Create tag {}", create_tag_matches.value_of("tag").unwrap());
+            let tag = create_tag_matches.value_of("tag").unwrap();
+            if let Err(error) = create_new_tag(&conn, tag) {
+                eprintln!("Couldn't create tag {}: {}", tag, error);
+                std::process::exit(1);
+            }
+            eprintln!("Create tag {}", tag);
         }
         ("show", Some(show_matches)) => {
+            let by_item = show_matches.is_present("by-item");
+            let fields = if by_item { ITEM_FIELDS } else { SHOW_FIELDS };
+            let options = ShowOptions {
+                kinds: &kinds,
+                long: show_matches.is_present("long"),
+                columns: show_matches.value_of("columns").map(|columns| columns_of(columns, fields)),
+                by_item,
+                format: format_of(show_matches, fields).unwrap(),
+                nul_delimited: show_matches.is_present("nul"),
+                tagged: time_range_of(show_matches),
+                sort: show_matches.value_of("sort").map(|sort| sort.parse().unwrap()),
+            };
             if show_matches.is_present("all") {
-            show_all(&conn);
+                show_all(&conn, &mut io::stdout().lock(), &options).unwrap();
             }
             else if show_matches.is_present("tags") {
-                let tag_vec: Vec<String> = show_matches.value_of("tags").unwrap().split(',').map(|c| "'".to_string() + c + "'").collect();
-                let tags = tag_vec.join(",");
-                show_tags(&conn, tags);
-                println!("tags is present. Value: {:?}", show_matches.value_of("tags").unwrap().split(',').collect::<Vec<&str>>());
+                let tag_vec: Vec<String> = show_matches.value_of("tags").unwrap().split(',').map(String::from).collect();
+                for tag in &tag_vec {
+                    if let Err(error) = query::Comparison::parse(tag) {
+                        eprintln!("Invalid tag '{}': {}", tag, error);
+                        std::process::exit(1);
+                    }
+                }
+                show_tags(&conn, &mut io::stdout().lock(), &options, &tag_vec).unwrap();
             }
             else if show_matches.is_present("paths") {
-                let path_vec = show_matches.value_of("paths").unwrap().split(',').map(|w| String::from(w)).collect::<Vec<String>>();
-                println!("paths is present");
-                show_paths(&conn, path_vec);
+                let path_vec: Vec<String> = show_matches.value_of("paths").unwrap().split(',').map(String::from).collect();
+                show_paths(&conn, &mut io::stdout().lock(), &options, &path_vec).unwrap();
             }
             else {
                 panic!("Didn't find anything in search which I can work with!!!")
             }
         }
+        ("log", Some(log_matches)) => {
+            let limit = log_matches.value_of("limit").map(|limit| limit.parse().unwrap());
+            let records: Vec<Vec<serde_json::Value>> = get_history(&conn, limit)
+                .unwrap()
+                .into_iter()
+                .map(|operation| {
+                    let arguments = serde_json::from_str(&operation.arguments).unwrap_or(operation.arguments.into());
+                    vec![
+                        operation.id.into(),
+                        operation.time.into(),
+                        operation.operation.into(),
+                        arguments,
+                        operation.affected_rows.into(),
+                        operation.undone_by.into(),
+                    ]
+                })
+                .collect();
+            let format = format_of(log_matches, LOG_FIELDS).unwrap();
+            to_stdout(|out| write_records(out, &format, &["ID", "TIME", "OPERATION", "ARGUMENTS", "ROWS", "UNDONE_BY"], &records));
+        }
+        ("undo", Some(undo_matches)) => {
+            let count = undo_matches.value_of("count").unwrap().parse().unwrap();
+            match undo(&conn, count) {
+                Ok(operations) if operations.is_empty() => eprintln!("Nothing to undo"),
+                Ok(operations) => {
+                    for operation in operations {
+                        eprintln!("Undid {} {} {}", operation.id, operation.operation, operation.arguments);
+                    }
+                }
+                Err(error) => {
+                    eprintln!("Couldn't undo, nothing was changed: {}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        ("tags-of", Some(tags_of_matches)) => run_tags_of_command(&conn, &kinds, &db, tags_of_matches),
+        ("note", Some(note_matches)) => {
+            let path = stored_path_of(&kinds, &db, note_matches.value_of("path").unwrap());
+            match note_matches.value_of("text") {
+                Some(text) => {
+                    let note = Some(text.trim()).filter(|note| !note.is_empty());
+                    if !set_item_note(&conn, &path, note).unwrap() {
+                        eprintln!("Path {} is not tagged", path);
+                        std::process::exit(1);
+                    }
+                }
+                None => match get_item_note(&conn, &path).unwrap() {
+                    Some(note) => println!("{}", note.unwrap_or_default()),
+                    None => {
+                        eprintln!("Path {} is not tagged", path);
+                        std::process::exit(1);
+                    }
+                },
+            }
+        }
+        ("describe", Some(describe_matches)) => {
+            let tag = describe_matches.value_of("tag").unwrap();
+            match describe_matches.value_of("text") {
+                Some(text) => {
+                    let description = Some(text.trim()).filter(|description| !description.is_empty());
+                    if !set_tag_description(&conn, tag, description).unwrap() {
+                        eprintln!("There is no tag {}", tag);
+                        std::process::exit(1);
+                    }
+                }
+                None => match get_tag_description(&conn, tag).unwrap() {
+                    Some(description) => println!("{}", description.unwrap_or_default()),
+                    None => {
+                        eprintln!("There is no tag {}", tag);
+                        std::process::exit(1);
+                    }
+                },
+            }
+        }
         ("delete", Some(delete_matches)) => {
-            println!("inside delete");
+            let mut ids = Vec::new();
             if delete_matches.is_present("tags") {
-                println!("tags are present");
-                let tag_vec: Vec<String> = delete_matches.value_of("tags").unwrap().split(',').map(|c| "'".to_string() + c + "'").collect();
-                println!("these are the tags");
-                delete_by_tag(&conn, tag_vec);
+                let tag_vec: Vec<String> = delete_matches.value_of("tags").unwrap().split(',').map(String::from).collect();
+                ids.extend(get_ids_of_tags(&conn, &tag_vec).unwrap());
             }
             if delete_matches.is_present("ids") {
-                let ids: String  = String::from(delete_matches.value_of("ids").unwrap());
-                delete_by_id(&conn, ids);
+                ids.extend(delete_matches.value_of("ids").unwrap().split(',').map(|id| {
+                    id.trim().parse::<i32>().unwrap_or_else(|_| {
+                        eprintln!("Invalid id '{}'", id);
+                        std::process::exit(1);
+                    })
+                }));
+            }
+            let mut descendants = Vec::new();
+            for id in &ids {
+                descendants.extend(get_descendant_ids(&conn, *id).unwrap());
             }
+            descendants.retain(|id| !ids.contains(id));
+            if !descendants.is_empty() && (delete_matches.is_present("cascade")
+                || confirm(format!("The tags to delete have {} child tags. Delete them too?", descendants.len()).as_str()))
+            {
+                ids.extend(descendants);
+            }
+            let deleted = delete_by_id(&conn, &ids).unwrap();
+            eprintln!("Deleted {} tags", deleted);
+        }
+        ("repair", Some(repair_matches)) => {
+            run_repair_command(&conn, &db, repair_matches);
+        }
+        ("check", Some(check_matches)) => {
+            run_check_command(&conn, &kinds, &db, check_matches);
         }
+        ("watch", Some(watch_matches)) => {
+            run_watch_command(&conn, &db, watch_matches);
+        }
+        ("untag", Some(untag_matches)) => {
+            let tag = untag_matches.value_of("tag").unwrap();
+            let paths: Vec<String> =
+                untag_matches.values_of("path").unwrap().map(|path| stored_path_of(&kinds, &db, path)).collect();
+            in_operation(&conn, "untag", json!({ "tag": tag, "paths": paths }), || {
+                for stored_path in &paths {
+                    if untag_path(&conn, tag, stored_path.as_str())? == 0 {
+                        eprintln!("Path {} is not tagged with {}", stored_path, tag);
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+        }
+        ("forget", Some(forget_matches)) => {
+            let paths: Vec<String> =
+                forget_matches.values_of("path").unwrap().map(|path| stored_path_of(&kinds, &db, path)).collect();
+            in_operation(&conn, "forget", json!({ "paths": paths }), || {
+                for stored_path in &paths {
+                    match forget_path(&conn, stored_path.as_str())? {
+                        0 => eprintln!("Path {} is not tagged", stored_path),
+                        n => eprintln!("Removed {} from {} tags", stored_path, n),
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+        }
+        ("tags", Some(tags_matches)) => {
+            if tags_matches.is_present("tree") {
+                show_tag_tree(&conn).unwrap();
+            } else {
+                let sort = tags_matches.value_of("sort").unwrap().parse().unwrap();
+                let min_count = tags_matches.value_of("min-count").map_or(0, |count| count.parse().unwrap());
+                let tags = get_tag_usage(&conn, sort, min_count, tags_matches.is_present("unused")).unwrap();
+                let records: Vec<Vec<serde_json::Value>> = tags
+                    .into_iter()
+                    .map(|tag| vec![tag.tag_name.into(), tag.count.into(), tag.time_created.into(), tag.time_last_used.into()])
+                    .collect();
+                let format = format_of(tags_matches, TAG_FIELDS).unwrap();
+                to_stdout(|out| write_records(out, &format, &["NAME", "COUNT", "CREATED", "LAST_USED"], &records));
+            }
+        }
+        ("alias", Some(alias_matches)) => match alias_matches.subcommand() {
+            ("add", Some(add_matches)) => {
+                let alias = add_matches.value_of("alias").unwrap();
+                let tag = add_matches.value_of("tag").unwrap();
+                // a failed alias must not leave a freshly created tag behind
+                let tx = conn.unchecked_transaction().unwrap();
+                if let Err(error) = add_alias(&tx, alias, tag) {
+                    eprintln!("Couldn't add alias {} for tag {}: {}", alias, tag, error);
+                    std::process::exit(1);
+                }
+                tx.commit().unwrap();
+            }
+            ("rm", Some(rm_matches)) => {
+                for alias in rm_matches.values_of("alias").unwrap() {
+                    if !remove_alias(&conn, alias).unwrap() {
+                        eprintln!("There is no alias {}", alias);
+                    }
+                }
+            }
+            ("list", Some(_)) => {
+                for (alias, tag) in get_aliases(&conn).unwrap() {
+                    println!("{} -> {}", alias, tag);
+                }
+            }
+            _ => unreachable!(),
+        },
         _ => unreachable!(), // If all subcommands are defined above, anything else is unreachable!()
     }
 
@@ -187,19 +663,493 @@ fn main() {
     // Continued program logic goes here...
 }
 
-fn tag_path(conn: Connection, path_as_str: Option<&str>, tag: Option<&str>) {
-    let path = PathBuf::from(path_as_str.unwrap());
-    match fs::canonicalize(&path) {
-        Ok(path) => {
-            println!("This will be saved to the db: {}", path.to_str().unwrap());
-            insert_path(&conn, path.to_str().unwrap(), tag.unwrap());
+/// Lists the tags of each path. Exits with 1 if a path isn't tagged.
+fn run_tags_of_command(conn: &Connection, kinds: &Registry, db: &DbLocation, matches: &ArgMatches) {
+    let args: Vec<&str> = matches.values_of("paths").unwrap().collect();
+    let mut untagged = false;
+    let mut records: Vec<Vec<serde_json::Value>> = Vec::new();
+    for arg in &args {
+        let path = stored_path_of(kinds, db, arg);
+        match get_tags_of_path(conn, &path).unwrap() {
+            Some(tags) => records.push(vec![path.into(), tags.into()]),
+            None => {
+                eprintln!("Path {} is not tagged", path);
+                untagged = true;
+            }
+        }
+    }
+    if let Some(format) = format_of(matches, &["path", "tags"]) {
+        to_stdout(|out| write_records(out, &format, &["PATH", "TAGS"], &records));
+    } else {
+        let several = args.len() > 1;
+        to_stdout(|out| {
+            for record in &records {
+                if several {
+                    writeln!(out, "{}:", record[0].as_str().unwrap_or_default())?;
+                }
+                for tag in record[1].as_array().into_iter().flatten() {
+                    writeln!(out, "{}{}", if several { "  " } else { "" }, tag.as_str().unwrap_or_default())?;
+                }
+            }
+            Ok(())
+        });
+    }
+    if untagged {
+        std::process::exit(1);
+    }
+}
+
+/// Fields of the rows of `rtag log`.
+const LOG_FIELDS: &[&str] = &["id", "time", "operation", "arguments", "rows", "undone_by"];
+
+/// Fields of the rows of `rtag tags`.
+const TAG_FIELDS: &[&str] = &["name", "count", "created", "last_used"];
+
+fn time_range_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+    let validate = |time: String| parse_time(&time, Utc::now()).map(|_| ());
+    [
+        Arg::with_name("since")
+            .long("since")
+            .takes_value(true)
+            .validator(validate)
+            .help("Only include what was tagged at or after this time, e.g. 7d, 12h or 2026-01-01"),
+        Arg::with_name("before")
+            .long("before")
+            .takes_value(true)
+            .validator(validate)
+            .help("Only include what was tagged before this time, e.g. 30d or 2026-01-01"),
+    ]
+}
+
+fn time_range_of(matches: &ArgMatches) -> TimeRange {
+    let now = Utc::now();
+    let time_of = |name| matches.value_of(name).map(|time| parse_time(time, now).unwrap());
+    TimeRange { since: time_of("since"), before: time_of("before") }
+}
+
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("format")
+        .long("format")
+        .takes_value(true)
+        .validator(|format| format.parse::<Format>().map(|_| ()))
+}
+
+/// Returns the `--format` of a command that writes `fields`. Exits if a
+/// template uses other fields.
+fn format_of(matches: &ArgMatches, fields: &[&str]) -> Option<Format> {
+    let format: Format = matches.value_of("format")?.parse().unwrap();
+    if let Format::Template(template) = &format {
+        let unknown: Vec<&str> = template.fields().into_iter().filter(|field| !fields.contains(field)).collect();
+        if !unknown.is_empty() {
+            eprintln!("Unknown fields in the template: {}, expected {}", unknown.join(", "), fields.join(", "));
+            std::process::exit(1);
+        }
+    }
+    Some(format)
+}
+
+/// Parses the `--columns` of `show`. Exits if a column isn't one of `fields`.
+fn columns_of(columns: &str, fields: &[&str]) -> Vec<String> {
+    let columns: Vec<&str> = columns.split(',').map(str::trim).filter(|column| !column.is_empty()).collect();
+    let unknown: Vec<&str> = columns.iter().copied().filter(|column| !fields.contains(column)).collect();
+    if !unknown.is_empty() {
+        eprintln!("Unknown columns: {}, expected {}", unknown.join(", "), fields.join(", "));
+        std::process::exit(1);
+    }
+    columns.into_iter().map(String::from).collect()
+}
+
+fn nul_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("nul")
+        .short("0")
+        .help("Only write the paths, each followed by a NUL byte instead of a newline, e.g. for 'xargs -0'")
+}
+
+/// Returns the stored form of a path or URL given on the command line. Paths
+/// that no longer exist are taken relative to the current directory.
+fn stored_path_of(kinds: &Registry, db: &DbLocation, path_as_str: &str) -> String {
+    let kind = kinds.kind_of(path_as_str).expect("paths accept every argument");
+    if kind.name() != PATH_ITEM {
+        let context = Context { db, walk_options: None, fragment_policy: FragmentPolicy::Keep };
+        if let Some(stored) = kind.resolve(path_as_str, &context).ok().and_then(|stored| stored.into_iter().next()) {
+            return stored;
+        }
+    }
+    let path = fs::canonicalize(path_as_str)
+        .unwrap_or_else(|_| env::current_dir().unwrap().join(path_as_str));
+    db.to_stored_path(&path)
+}
+
+/// Reads NUL separated paths from stdin.
+fn read_stdin0() -> Vec<String> {
+    let mut input = Vec::new();
+    io::stdin().lock().read_to_end(&mut input).unwrap();
+    input
+        .split(|byte| *byte == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect()
+}
+
+fn walk_options_of(matches: &ArgMatches) -> WalkOptions {
+    let max_depth = matches.value_of("max-depth").map(|depth| {
+        depth.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid depth {}", depth);
+            std::process::exit(1);
+        })
+    });
+    let entry_type = match matches.value_of("type") {
+        Some("d") => EntryType::Dir,
+        _ => EntryType::File,
+    };
+    WalkOptions {
+        hidden: matches.is_present("hidden"),
+        max_depth,
+        entry_type,
+    }
+}
+
+/// Resolves the arguments to tag with their item kinds and returns the stored
+/// items grouped by kind. Exits if any argument can't be resolved, so that
+/// nothing is tagged.
+fn resolve_items(kinds: &Registry, args: &[String], context: &Context) -> BTreeMap<&'static str, Vec<String>> {
+    let mut items: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+    let mut errors = Vec::new();
+    for arg in args {
+        let kind = kinds.kind_of(arg).expect("paths accept every argument");
+        match kind.resolve(arg, context) {
+            Ok(stored) => items.entry(kind.name()).or_default().extend(stored),
+            Err(error) => errors.push(error),
+        }
+    }
+    if !errors.is_empty() {
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        eprintln!("Nothing was tagged");
+        std::process::exit(1);
+    }
+    for stored in items.values_mut() {
+        stored.sort();
+        stored.dedup();
+    }
+    items
+}
+
+/// Resolves the database location and makes sure its directory exists.
+fn resolve_db(matches: &ArgMatches) -> DbLocation {
+    // `--db` may be given before or after the subcommand
+    let cli_db = matches.value_of("db").or_else(|| {
+        matches.subcommand().1.and_then(|sub_matches| sub_matches.value_of("db"))
+    });
+    let cwd = env::current_dir().and_then(fs::canonicalize).unwrap_or_else(|error| {
+        eprintln!("Couldn't determine the current directory: {}", error);
+        std::process::exit(1);
+    });
+    let db = location::resolve_db_location(cli_db.map(Path::new), &cwd).unwrap_or_else(|| {
+        eprintln!("Couldn't determine the database location. Use --db or set {}", location::DB_ENV_VAR);
+        std::process::exit(1);
+    });
+    if let Some(parent) = db.path.parent() {
+        if let Err(error) = fs::create_dir_all(parent) {
+            eprintln!("Couldn't create database directory {}: {}", parent.display(), error);
+            std::process::exit(1);
+        }
+    }
+    db
+}
+
+fn run_tag_command(conn: &Connection, matches: &ArgMatches) {
+    match matches.subcommand() {
+        ("rename", Some(rename_matches)) => {
+            let old = rename_matches.value_of("old").unwrap();
+            let new = rename_matches.value_of("new").unwrap();
+            let has_children = get_ids_of_tags(conn, &[String::from(old)]).unwrap().iter()
+                .any(|id| !get_descendant_ids(conn, *id).unwrap().is_empty());
+            let cascade = has_children && (rename_matches.is_present("cascade")
+                || confirm(format!("Tag {} has child tags. Rename them too?", old).as_str()));
+            match rename_tag(conn, old, new, cascade) {
+                Ok(true) => eprintln!("Renamed tag {} to {}", old, new),
+                Ok(false) => {
+                    eprintln!("There is no tag {}", old);
+                    std::process::exit(1);
+                }
+                Err(error) => {
+                    eprintln!("Couldn't rename tag {} to {}: {}", old, new, error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        ("merge", Some(merge_matches)) => {
+            let tags: Vec<&str> = merge_matches.values_of("tags").unwrap().collect();
+            let (dst, sources) = match tags.split_last() {
+                Some((dst, [sources @ .., "into"])) if !sources.is_empty() => (*dst, sources),
+                _ => {
+                    eprintln!("Usage: rtag tag merge <src>... into <dst>");
+                    std::process::exit(1);
+                }
+            };
+            let sources: Vec<String> = sources.iter().map(|tag| String::from(*tag)).collect();
+            match merge_tags(conn, &sources, dst) {
+                Ok(missing) => {
+                    for tag in missing {
+                        eprintln!("There is no tag {}", tag);
+                    }
+                    eprintln!("Merged {} into {}", sources.join(", "), dst);
+                }
+                Err(error) => {
+                    eprintln!("Couldn't merge into {}: {}", dst, error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Asks a yes/no question on the terminal. Returns `false` without asking if
+/// stdin is not a terminal.
+fn confirm(question: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).unwrap();
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Asks to pick one of `paths` and returns it, or `None` if the question was
+/// skipped. Questions are skipped when stdin is not a terminal.
+fn choose(question: &str, paths: &[PathBuf]) -> Option<PathBuf> {
+    eprintln!("{}", question);
+    for (number, path) in paths.iter().enumerate() {
+        eprintln!("  {}) {}", number + 1, path.display());
+    }
+    if !io::stdin().is_terminal() {
+        eprintln!("Skipped, run in a terminal to choose");
+        return None;
+    }
+    eprint!("Choose 1-{} or press enter to skip: ", paths.len());
+    io::stderr().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).unwrap();
+    answer.trim().parse::<usize>().ok()
+        .filter(|number| (1..=paths.len()).contains(number))
+        .map(|number| paths[number - 1].clone())
+}
+
+/// Records the fingerprints of freshly tagged paths for 'rtag repair'.
+fn record_fingerprints(conn: &Connection, db: &DbLocation, paths: &[String], hash: bool) {
+    for path in paths {
+        match Fingerprint::of(&db.to_absolute_path(path), hash) {
+            Ok(fingerprint) => set_fingerprint(conn, path, &fingerprint).unwrap(),
+            Err(error) => eprintln!("Couldn't read the fingerprint of {}: {}", path, error),
+        }
+    }
+}
+
+/// Looks for tagged files that no longer exist below the given roots and
+/// moves their tags to the new location.
+fn run_repair_command(conn: &Connection, db: &DbLocation, matches: &ArgMatches) {
+    let mut missing: Vec<(String, Fingerprint)> = Vec::new();
+    // items tagged before fingerprints were recorded can't be recognized
+    let mut unknown: Vec<String> = Vec::new();
+    for (path, fingerprint) in get_path_items(conn).unwrap() {
+        if fs::symlink_metadata(db.to_absolute_path(&path)).is_ok() {
+            continue;
+        }
+        match fingerprint {
+            Some(fingerprint) => missing.push((path, fingerprint)),
+            None => {
+                eprintln!("Couldn't repair {}: no fingerprint was recorded", path);
+                unknown.push(path);
+            }
+        }
+    }
+    if missing.is_empty() {
+        if unknown.is_empty() {
+            eprintln!("No tagged files are missing");
+        }
+        return;
+    }
+
+    let tracked: HashSet<PathBuf> = get_item_paths(conn).unwrap().iter()
+        .map(|path| db.to_absolute_path(path))
+        .collect();
+    let options = WalkOptions { hidden: true, max_depth: None, entry_type: EntryType::Any };
+    let mut candidates = Vec::new();
+    for root in matches.values_of("root").unwrap() {
+        let root = fs::canonicalize(root).unwrap_or_else(|error| {
+            eprintln!("Couldn't find the path {}: {}", root, error);
+            std::process::exit(1);
+        });
+        for path in walk_dir(&root, &options) {
+            if !tracked.contains(&path) {
+                if let Ok(fingerprint) = Fingerprint::of(&path, false) {
+                    candidates.push((path, fingerprint));
+                }
+            }
+        }
+    }
+
+    let mut hashes: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut repaired = 0;
+    for (path, fingerprint) in &missing {
+        let hash_of = |candidate: &Path| {
+            hashes.entry(candidate.to_path_buf()).or_insert_with(|| content_hash(candidate).ok()).clone()
+        };
+        let new_path = match find_moved(fingerprint, &candidates, hash_of) {
+            Some(Match::Certain(new_path)) => Some(new_path),
+            Some(Match::Ambiguous(new_paths)) => choose(format!("{} may have been moved to", path).as_str(), &new_paths),
+            None => {
+                eprintln!("Couldn't find {}", path);
+                None
+            }
+        };
+        if let Some(new_path) = new_path {
+            let new_fingerprint = Fingerprint::of(&new_path, fingerprint.content_hash.is_some()).unwrap();
+            let new_stored_path = db.to_stored_path(&new_path);
+            move_item(conn, path, &new_stored_path, &new_fingerprint).unwrap();
+            eprintln!("Moved {} to {}", path, new_stored_path);
+            candidates.retain(|(candidate, _)| *candidate != new_path);
+            repaired += 1;
         }
-        Err(error) => panic!(
-            "Couldn't find the path {}. Received error: {:?}",
-            path_as_str.unwrap(),
-            error
-        ),
     }
+    eprintln!("Repaired {} of {} missing paths", repaired, missing.len() + unknown.len());
 }
 
-fn save_to_sqlite() {}
+fn run_check_command(conn: &Connection, kinds: &Registry, db: &DbLocation, matches: &ArgMatches) {
+    let mut findings: Vec<Finding> = get_path_items(conn).unwrap().into_iter()
+        .filter_map(|(path, fingerprint)| {
+            check_path(&db.to_absolute_path(&path), fingerprint.as_ref()).map(|problem| Finding { path, problem })
+        })
+        .collect();
+    // items of other kinds can only be checked for existence
+    for (path, item_type) in get_items(conn).unwrap() {
+        let exists = kinds.get(&item_type).and_then(|kind| kind.exists(&path, db));
+        if item_type != PATH_ITEM && exists == Some(false) {
+            findings.push(Finding { path, problem: Problem::Missing });
+        }
+    }
+
+    let mut pruned = 0;
+    if matches.is_present("prune") {
+        let tx = conn.unchecked_transaction().unwrap();
+        for finding in findings.iter().filter(|finding| finding.problem == Problem::Missing) {
+            forget_path(&tx, &finding.path).unwrap();
+            pruned += 1;
+        }
+        tx.commit().unwrap();
+    }
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&findings).unwrap());
+    } else {
+        for finding in &findings {
+            println!("{:<8} {}", finding.problem, finding.path);
+        }
+        if matches.is_present("prune") {
+            eprintln!("Forgot {} missing paths", pruned);
+        }
+    }
+
+    let dangling = findings.iter().filter(|finding| finding.problem.is_dangling()).count();
+    if dangling > pruned {
+        std::process::exit(1);
+    }
+}
+
+/// Watches the given or default directories until interrupted and applies
+/// renames and deletes of tagged paths to the store.
+fn run_watch_command(conn: &Connection, db: &DbLocation, matches: &ArgMatches) {
+    let roots: Vec<PathBuf> = match matches.values_of("root") {
+        Some(roots) => roots.map(|root| fs::canonicalize(root).unwrap_or_else(|error| {
+            eprintln!("Couldn't find the path {}: {}", root, error);
+            std::process::exit(1);
+        })).collect(),
+        None => match &db.root {
+            Some(root) => vec![root.clone()],
+            None => {
+                let paths: Vec<PathBuf> = get_item_paths(conn).unwrap().iter()
+                    .map(|path| db.to_absolute_path(path))
+                    .collect();
+                default_roots(&paths).into_iter().filter(|root| root.is_dir()).collect()
+            }
+        },
+    };
+    if roots.is_empty() {
+        eprintln!("Nothing to watch, tag some paths first");
+        std::process::exit(1);
+    }
+
+    // other rtag commands may write while we are watching
+    conn.busy_timeout(Duration::from_secs(10)).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).unwrap_or_else(|error| {
+        eprintln!("Couldn't start watching: {}", error);
+        std::process::exit(1);
+    });
+    for root in &roots {
+        if let Err(error) = watcher.watch(root, RecursiveMode::Recursive) {
+            eprintln!("Couldn't watch {}: {}", root.display(), error);
+            std::process::exit(1);
+        }
+        eprintln!("Watching {}", root.display());
+    }
+
+    for event in receiver {
+        let event = match event {
+            Ok(event) => event,
+            Err(error) => {
+                eprintln!("Watch error: {}", error);
+                continue;
+            }
+        };
+        for change in changes_of(&event) {
+            match apply_change(conn, db, &change) {
+                Ok(0) => {}
+                Ok(count) => match &change {
+                    Change::Moved(from, to) => eprintln!("Moved {} to {} ({} items)", from.display(), to.display(), count),
+                    Change::Removed(path) => eprintln!("Marked {} as missing ({} items)", path.display(), count),
+                    Change::Created(path) => eprintln!("Found {} again", path.display()),
+                },
+                Err(error) => eprintln!("Couldn't apply {:?}: {}", change, error),
+            }
+        }
+    }
+}
+
+fn init_local_db(dir: &Path) {
+    let db = location::init_local_db(dir).unwrap_or_else(|error| {
+        eprintln!("Couldn't create {} in {}: {}", location::LOCAL_DIR_NAME, dir.display(), error);
+        std::process::exit(1);
+    });
+    let existed = db.path.exists();
+    create_db_and_initialize_tables(&db.path).unwrap();
+    if existed {
+        eprintln!("Reinitialized existing rtag database in {}", db.path.display());
+    } else {
+        eprintln!("Initialized empty rtag database in {}", db.path.display());
+    }
+}
+
+fn run_db_command(db_path: &Path, matches: &ArgMatches) {
+    if let ("migrate", Some(migrate_matches)) = matches.subcommand() {
+        let conn = open_db(db_path).unwrap();
+        let version = migrations::current_version(&conn).unwrap();
+        let migrations = if migrate_matches.is_present("dry-run") {
+            migrations::pending_migrations(&conn).unwrap()
+        } else {
+            migrations::migrate(&conn).unwrap()
+        };
+        if migrations.is_empty() {
+            eprintln!("Schema is up to date at version {}", version);
+        }
+        for migration in migrations {
+            let verb = if migrate_matches.is_present("dry-run") { "Would apply" } else { "Applied" };
+            eprintln!("{} migration {}: {}", verb, migration.version, migration.description);
+        }
+    }
+}
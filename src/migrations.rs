@@ -0,0 +1,428 @@
+//! Versioned schema migrations for the SQLite store.
+//!
+//! Every database carries a `schema_version` table with one row per applied
+//! migration. On open, all migrations in [`MIGRATIONS`] with a higher version
+//! than the recorded one are applied in order, each in its own transaction.
+//! New schema changes are added by appending to [`MIGRATIONS`]; existing
+//! entries must never be edited once released.
+use rusqlite::{params, Connection, Result, NO_PARAMS};
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub up: fn(&Connection) -> Result<()>,
+}
+
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create dim_tag and fct_tag",
+        up: create_initial_tables,
+    },
+    Migration {
+        version: 2,
+        description: "index fct_tag on id and path",
+        up: index_fct_tag,
+    },
+    Migration {
+        version: 3,
+        description: "add parent_id to dim_tag for hierarchical tags",
+        up: add_tag_parents,
+    },
+    Migration {
+        version: 4,
+        description: "create tag_alias",
+        up: create_tag_alias,
+    },
+    Migration {
+        version: 5,
+        description: "forbid renaming a tag to an alias",
+        up: forbid_renaming_to_alias,
+    },
+    Migration {
+        version: 6,
+        description: "split dim_tag and fct_tag into items, tags and item_tags",
+        up: normalize_schema,
+    },
+    Migration {
+        version: 7,
+        description: "add file fingerprints to items",
+        up: add_item_fingerprints,
+    },
+    Migration {
+        version: 8,
+        description: "add missing_since to items",
+        up: add_item_missing_since,
+    },
+    Migration {
+        version: 9,
+        description: "add typed values to item_tags",
+        up: add_item_tag_values,
+    },
+    Migration {
+        version: 10,
+        description: "add notes to items and descriptions to tags",
+        up: add_notes_and_descriptions,
+    },
+    Migration {
+        version: 11,
+        description: "record when tags were last used",
+        up: add_tag_last_used,
+    },
+    Migration {
+        version: 12,
+        description: "record when items were tagged",
+        up: add_tagged_at,
+    },
+    Migration {
+        version: 13,
+        description: "record the history of changes for undo",
+        up: create_history,
+    },
+    Migration {
+        version: 14,
+        description: "record only the changed columns of updated rows in the history",
+        up: record_changed_columns,
+    },
+];
+
+/// Creates the original tables. `IF NOT EXISTS` lets databases created before
+/// versioning adopt this migration without losing data.
+fn create_initial_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS dim_tag (
+                id              INTEGER PRIMARY KEY,
+                tag_name VARCHAR UNIQUE,
+                time_created    TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+         CREATE TABLE IF NOT EXISTS fct_tag (
+                id  INTEGER,
+                path VARCHAR
+                );",
+    )
+}
+
+fn index_fct_tag(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_fct_tag_id ON fct_tag (id);
+         CREATE INDEX IF NOT EXISTS idx_fct_tag_path ON fct_tag (path);",
+    )
+}
+
+/// Adds `dim_tag.parent_id` and links existing tags like `lang/rust` to their
+/// parent, creating missing ancestors.
+fn add_tag_parents(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE dim_tag ADD COLUMN parent_id INTEGER REFERENCES dim_tag (id);
+         CREATE INDEX idx_dim_tag_parent_id ON dim_tag (parent_id);",
+    )?;
+    let mut stmt = conn.prepare("SELECT tag_name FROM dim_tag WHERE tag_name LIKE '%/%' ORDER BY length(tag_name)")?;
+    let names = stmt.query_map(NO_PARAMS, |row| row.get(0))?.collect::<Result<Vec<String>>>()?;
+    for name in names {
+        let mut child = name.as_str();
+        while let Some((parent, _)) = child.rsplit_once('/').filter(|(parent, _)| !parent.is_empty()) {
+            conn.execute("INSERT OR IGNORE INTO dim_tag (tag_name) VALUES (?1)", params![parent])?;
+            conn.execute(
+                "UPDATE dim_tag SET parent_id = (SELECT id FROM dim_tag WHERE tag_name = ?1) WHERE tag_name = ?2",
+                params![parent, child],
+            )?;
+            child = parent;
+        }
+    }
+    Ok(())
+}
+
+/// Creates `tag_alias`. The triggers keep the names of aliases and tags
+/// disjoint, so every name resolves to exactly one tag.
+fn create_tag_alias(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE tag_alias (
+                alias   VARCHAR PRIMARY KEY,
+                tag_id  INTEGER NOT NULL REFERENCES dim_tag (id)
+                );
+         CREATE INDEX idx_tag_alias_tag_id ON tag_alias (tag_id);
+         CREATE TRIGGER tag_alias_is_no_tag BEFORE INSERT ON tag_alias
+         WHEN EXISTS (SELECT 1 FROM dim_tag WHERE tag_name = NEW.alias)
+         BEGIN SELECT RAISE(ABORT, 'an alias cannot have the name of an existing tag'); END;
+         CREATE TRIGGER dim_tag_is_no_alias BEFORE INSERT ON dim_tag
+         WHEN EXISTS (SELECT 1 FROM tag_alias WHERE alias = NEW.tag_name)
+         BEGIN SELECT RAISE(ABORT, 'a tag cannot have the name of an existing alias'); END;",
+    )
+}
+
+fn forbid_renaming_to_alias(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TRIGGER dim_tag_rename_is_no_alias BEFORE UPDATE OF tag_name ON dim_tag
+         WHEN EXISTS (SELECT 1 FROM tag_alias WHERE alias = NEW.tag_name)
+         BEGIN SELECT RAISE(ABORT, 'a tag cannot have the name of an existing alias'); END;",
+    )
+}
+
+/// Replaces `dim_tag` and `fct_tag`, where `fct_tag.id` doubled as the tag
+/// id, with separate `items`, `tags` and `item_tags` tables. Tag ids are kept;
+/// duplicate and dangling associations are dropped.
+fn normalize_schema(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE items (
+                id              INTEGER PRIMARY KEY,
+                path            VARCHAR NOT NULL UNIQUE,
+                type            VARCHAR NOT NULL DEFAULT 'path',
+                time_created    TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                time_updated    TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+         CREATE TABLE tags (
+                id              INTEGER PRIMARY KEY,
+                tag_name        VARCHAR NOT NULL UNIQUE,
+                parent_id       INTEGER REFERENCES tags (id) ON DELETE SET NULL,
+                time_created    TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+         CREATE INDEX idx_tags_parent_id ON tags (parent_id);
+         CREATE TABLE item_tags (
+                id       INTEGER PRIMARY KEY,
+                item_id  INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+                tag_id   INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+                UNIQUE (item_id, tag_id)
+                );
+         CREATE INDEX idx_item_tags_tag_id ON item_tags (tag_id);
+
+         INSERT INTO tags (id, tag_name, parent_id, time_created)
+         SELECT id, tag_name, parent_id, time_created FROM dim_tag WHERE tag_name IS NOT NULL;
+         UPDATE tags SET parent_id = NULL WHERE parent_id NOT IN (SELECT id FROM tags);
+         INSERT INTO items (path) SELECT DISTINCT path FROM fct_tag
+         WHERE path IS NOT NULL AND id IN (SELECT id FROM tags);
+         INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+         SELECT items.id, fct_tag.id FROM fct_tag JOIN items ON items.path = fct_tag.path
+         WHERE fct_tag.id IN (SELECT id FROM tags);
+
+         ALTER TABLE tag_alias RENAME TO old_tag_alias;
+         CREATE TABLE tag_alias (
+                alias   VARCHAR PRIMARY KEY,
+                tag_id  INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE
+                );
+         INSERT INTO tag_alias (alias, tag_id)
+         SELECT alias, tag_id FROM old_tag_alias WHERE tag_id IN (SELECT id FROM tags);
+         DROP INDEX idx_tag_alias_tag_id;
+         CREATE INDEX idx_tag_alias_tag_id ON tag_alias (tag_id);
+
+         DROP TABLE old_tag_alias;
+         DROP TABLE fct_tag;
+         DROP TABLE dim_tag;
+
+         CREATE TRIGGER tag_alias_is_no_tag BEFORE INSERT ON tag_alias
+         WHEN EXISTS (SELECT 1 FROM tags WHERE tag_name = NEW.alias)
+         BEGIN SELECT RAISE(ABORT, 'an alias cannot have the name of an existing tag'); END;
+         CREATE TRIGGER tags_is_no_alias BEFORE INSERT ON tags
+         WHEN EXISTS (SELECT 1 FROM tag_alias WHERE alias = NEW.tag_name)
+         BEGIN SELECT RAISE(ABORT, 'a tag cannot have the name of an existing alias'); END;
+         CREATE TRIGGER tags_rename_is_no_alias BEFORE UPDATE OF tag_name ON tags
+         WHEN EXISTS (SELECT 1 FROM tag_alias WHERE alias = NEW.tag_name)
+         BEGIN SELECT RAISE(ABORT, 'a tag cannot have the name of an existing alias'); END;",
+    )
+}
+
+/// Adds the device, inode, size, modification time and optional content hash
+/// of a file, used to find it again after it was moved.
+fn add_item_fingerprints(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE items ADD COLUMN dev INTEGER;
+         ALTER TABLE items ADD COLUMN inode INTEGER;
+         ALTER TABLE items ADD COLUMN size INTEGER;
+         ALTER TABLE items ADD COLUMN mtime INTEGER;
+         ALTER TABLE items ADD COLUMN content_hash VARCHAR;
+         CREATE INDEX idx_items_dev_inode ON items (dev, inode);",
+    )
+}
+
+/// Adds the time an item was found to be deleted, `NULL` while it exists.
+fn add_item_missing_since(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE items ADD COLUMN missing_since TIMESTAMP;")
+}
+
+/// Adds a value like the `4` of `rating=4` to every association. The value is
+/// stored with its SQLite type and `value_type` is one of `int`, `float`,
+/// `date` or `string`.
+fn add_item_tag_values(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE item_tags ADD COLUMN value;
+         ALTER TABLE item_tags ADD COLUMN value_type VARCHAR;
+         CREATE INDEX idx_item_tags_tag_id_value ON item_tags (tag_id, value);",
+    )
+}
+
+fn add_notes_and_descriptions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE items ADD COLUMN note VARCHAR;
+         ALTER TABLE tags ADD COLUMN description VARCHAR;",
+    )
+}
+
+/// Adds `tags.time_last_used`, set whenever a tag is applied to an item. It
+/// is unknown for tags applied before this migration.
+fn add_tag_last_used(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE tags ADD COLUMN time_last_used TIMESTAMP;")
+}
+
+/// Adds `item_tags.tagged_at`, unknown for associations made before this
+/// migration.
+fn add_tagged_at(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE item_tags ADD COLUMN tagged_at TIMESTAMP;
+         CREATE INDEX idx_item_tags_tagged_at ON item_tags (tagged_at);",
+    )
+}
+
+/// Tables whose changes are recorded in the history, with their columns as of
+/// [`create_history`]. A later migration that adds columns to these tables
+/// must recreate their history triggers.
+const HISTORY_TABLES: &[(&str, &[&str])] = &[
+    ("tags", &["id", "tag_name", "parent_id", "time_created", "description", "time_last_used"]),
+    (
+        "items",
+        &[
+            "id",
+            "path",
+            "type",
+            "time_created",
+            "time_updated",
+            "dev",
+            "inode",
+            "size",
+            "mtime",
+            "content_hash",
+            "missing_since",
+            "note",
+        ],
+    ),
+    ("item_tags", &["id", "item_id", "tag_id", "value", "value_type", "tagged_at"]),
+    ("tag_alias", &["rowid", "alias", "tag_id"]),
+];
+
+/// Creates the append-only `history` of operations and the `history_changes`
+/// with the rows each operation changed, see [`crate::history`]. Changes are
+/// recorded by triggers while `history_current` names the running operation.
+fn create_history(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE history (
+                id          INTEGER PRIMARY KEY,
+                operation   VARCHAR NOT NULL,
+                arguments   VARCHAR NOT NULL,
+                time        TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+         CREATE TABLE history_changes (
+                id          INTEGER PRIMARY KEY,
+                history_id  INTEGER NOT NULL REFERENCES history (id),
+                table_name  VARCHAR NOT NULL,
+                action      VARCHAR NOT NULL,
+                row_id      INTEGER NOT NULL,
+                old_row     VARCHAR
+                );
+         CREATE INDEX idx_history_changes_history_id ON history_changes (history_id);
+         CREATE TABLE history_current (history_id INTEGER NOT NULL);
+         CREATE TRIGGER history_no_update BEFORE UPDATE ON history
+         BEGIN SELECT RAISE(ABORT, 'the history is append-only'); END;
+         CREATE TRIGGER history_no_delete BEFORE DELETE ON history
+         BEGIN SELECT RAISE(ABORT, 'the history is append-only'); END;
+         CREATE TRIGGER history_changes_no_update BEFORE UPDATE ON history_changes
+         BEGIN SELECT RAISE(ABORT, 'the history is append-only'); END;
+         CREATE TRIGGER history_changes_no_delete BEFORE DELETE ON history_changes
+         BEGIN SELECT RAISE(ABORT, 'the history is append-only'); END;",
+    )?;
+    for (table, columns) in HISTORY_TABLES {
+        let old_row = format!(
+            "json_object({})",
+            columns.iter().map(|column| format!("'{column}', OLD.{column}", column = column)).collect::<Vec<_>>().join(", ")
+        );
+        for (event, row_id, old_row) in &[
+            ("INSERT", "NEW.rowid", "NULL"),
+            ("UPDATE", "OLD.rowid", old_row.as_str()),
+            ("DELETE", "OLD.rowid", old_row.as_str()),
+        ] {
+            conn.execute_batch(&format!(
+                "CREATE TRIGGER history_{table}_{action} AFTER {event} ON {table}
+                 WHEN EXISTS (SELECT 1 FROM history_current)
+                 BEGIN
+                    INSERT INTO history_changes (history_id, table_name, action, row_id, old_row)
+                    SELECT history_id, '{table}', '{action}', {row_id}, {old_row} FROM history_current;
+                 END;",
+                table = table,
+                action = event.to_lowercase(),
+                event = event,
+                row_id = row_id,
+                old_row = old_row
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the update triggers of [`create_history`] by ones that record
+/// only the columns an update changed, so undoing it leaves the columns that
+/// later operations changed alone. Updates that change nothing are not
+/// recorded.
+fn record_changed_columns(conn: &Connection) -> Result<()> {
+    for (table, columns) in HISTORY_TABLES {
+        let changed = |column: &&str| format!("OLD.{column} IS NOT NEW.{column}", column = column);
+        let old_values = columns
+            .iter()
+            .map(|column| format!("SELECT '{column}' AS name, OLD.{column} AS value WHERE {}", changed(column), column = column))
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        conn.execute_batch(&format!(
+            "DROP TRIGGER history_{table}_update;
+             CREATE TRIGGER history_{table}_update AFTER UPDATE ON {table}
+             WHEN EXISTS (SELECT 1 FROM history_current) AND ({changed})
+             BEGIN
+                INSERT INTO history_changes (history_id, table_name, action, row_id, old_row)
+                SELECT history_id, '{table}', 'update', OLD.rowid,
+                       (SELECT json_group_object(name, value) FROM ({old_values}))
+                FROM history_current;
+             END;",
+            table = table,
+            changed = columns.iter().map(changed).collect::<Vec<_>>().join(" OR "),
+            old_values = old_values
+        ))?;
+    }
+    Ok(())
+}
+
+/// Returns the schema version of the database, `0` if it was never migrated.
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    let has_table: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version')",
+        NO_PARAMS,
+        |row| row.get(0),
+    )?;
+    if !has_table {
+        return Ok(0);
+    }
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", NO_PARAMS, |row| row.get(0))
+}
+
+/// Returns the migrations that have not been applied yet, in order.
+pub fn pending_migrations(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let version = current_version(conn)?;
+    Ok(MIGRATIONS.iter().filter(|m| m.version > version).collect())
+}
+
+/// Applies all pending migrations and returns the ones that were applied.
+pub fn migrate(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+                version     INTEGER PRIMARY KEY,
+                description VARCHAR,
+                applied_at  TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )",
+        NO_PARAMS,
+    )?;
+    let pending = pending_migrations(conn)?;
+    for migration in &pending {
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description) VALUES (?1, ?2)",
+            params![migration.version, migration.description],
+        )?;
+        tx.commit()?;
+    }
+    Ok(pending)
+}
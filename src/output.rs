@@ -0,0 +1,274 @@
+//! Output formats of `show` and `search`.
+//!
+//! Results are written as records with named fields. Tables are meant for
+//! people, the other formats for pipelines and other programs: JSON and NDJSON
+//! objects keyed by the lowercase field names, CSV as in RFC 4180 and TSV with
+//! tabs, newlines and backslashes escaped as `\t`, `\n` and `\\`.
+//!
+//! A [`Template`] such as `{path}\t{tags|join(",")}` writes one line per
+//! record instead.
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use prettytable::{Cell, Row, Table};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    Template(Template),
+}
+
+pub const FORMAT_NAMES: &[&str] = &["table", "json", "ndjson", "csv", "tsv"];
+
+impl FromStr for Format {
+    type Err = String;
+
+    /// Parses a format name, or a template if it contains a `{`.
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name {
+            _ if name.contains('{') => name.parse().map(Format::Template),
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(format!("unknown format {}, expected one of {} or a template", name, FORMAT_NAMES.join(", "))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    /// A field, with the separator of its elements if it is a list.
+    Field { name: String, separator: String },
+}
+
+/// A line written for each record, e.g. `{path}\t{tags|join(",")}`.
+///
+/// `{field}` is replaced by the value of the field; lists are joined with
+/// commas, or the separator given with `|join("...")`. `{{` and `}}` are
+/// literal braces, and `\t`, `\n`, `\0` and `\\` are escapes, so templates
+/// can be written in single quotes in a shell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('0') => result.push('\0'),
+            Some(other) if other != '\\' => {
+                result.push('\\');
+                result.push(other);
+            }
+            _ => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn parse_field(field: &str) -> Result<Piece, String> {
+    let (name, filter) = match field.split_once('|') {
+        Some((name, filter)) => (name.trim(), Some(filter.trim())),
+        None => (field.trim(), None),
+    };
+    if name.is_empty() {
+        return Err(format!("missing field name in {{{}}}", field));
+    }
+    let separator = match filter {
+        None => String::from(","),
+        Some(filter) => filter
+            .strip_prefix("join(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .map(str::trim)
+            .and_then(|argument| argument.strip_prefix('"'))
+            .and_then(|argument| argument.strip_suffix('"'))
+            .map(unescape)
+            .ok_or_else(|| format!("unknown filter {} in {{{}}}, expected join(\"...\")", filter, field))?,
+    };
+    Ok(Piece::Field { name: name.to_lowercase(), separator })
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Template, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let (brace, after) = (&rest[i..i + 1], &rest[i + 1..]);
+            if let Some(after) = after.strip_prefix(brace) {
+                text.push_str(brace);
+                rest = after;
+                continue;
+            }
+            if brace == "}" {
+                return Err(String::from("unmatched } in the template, write }} for a literal brace"));
+            }
+            let end = after.find('}').ok_or("unclosed { in the template, write {{ for a literal brace")?;
+            if !text.is_empty() {
+                pieces.push(Piece::Text(unescape(&text)));
+                text.clear();
+            }
+            pieces.push(parse_field(&after[..end])?);
+            rest = &after[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            pieces.push(Piece::Text(unescape(&text)));
+        }
+        Ok(Template { pieces })
+    }
+}
+
+impl Template {
+    /// Returns the names of the fields used in the template.
+    pub fn fields(&self) -> Vec<&str> {
+        self.pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Field { name, .. } => Some(name.as_str()),
+                Piece::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// Renders a record whose fields are named by `headers`. Fields that
+    /// aren't in `headers` are empty.
+    pub fn render(&self, headers: &[&str], record: &[Value]) -> String {
+        let mut line = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => line.push_str(text),
+                Piece::Field { name, separator } => {
+                    if let Some(i) = headers.iter().position(|header| header.eq_ignore_ascii_case(name)) {
+                        line.push_str(&joined_text(&record[i], separator));
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+/// Returns the text of a field in tables, CSV and TSV. Lists are joined
+/// with commas.
+fn field_text(value: &Value) -> String {
+    joined_text(value, ",")
+}
+
+fn joined_text(value: &Value, separator: &str) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(values) => values.iter().map(field_text).collect::<Vec<String>>().join(separator),
+        other => other.to_string(),
+    }
+}
+
+fn to_object(headers: &[&str], record: &[Value]) -> Value {
+    let object: Map<String, Value> =
+        headers.iter().map(|header| header.to_lowercase()).zip(record.iter().cloned()).collect();
+    Value::Object(object)
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
+    }
+}
+
+fn tsv_field(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// Writes `records`, whose fields are named by `headers`, in `format`.
+pub fn write_records<W: Write>(out: &mut W, format: &Format, headers: &[&str], records: &[Vec<Value>]) -> io::Result<()> {
+    match format {
+        Format::Table => {
+            let mut table = Table::new();
+            table.add_row(Row::from(headers));
+            for record in records {
+                table.add_row(Row::new(record.iter().map(|value| Cell::new(&field_text(value))).collect()));
+            }
+            table.print(out)?;
+        }
+        Format::Json => {
+            let objects: Vec<Value> = records.iter().map(|record| to_object(headers, record)).collect();
+            serde_json::to_writer_pretty(&mut *out, &objects)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *out, &to_object(headers, record))?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv | Format::Tsv => {
+            let (separator, escape): (&str, fn(&str) -> String) = match format {
+                Format::Csv => (",", csv_field),
+                _ => ("\t", tsv_field),
+            };
+            let header_line: Vec<String> = headers.iter().map(|header| escape(header)).collect();
+            writeln!(out, "{}", header_line.join(separator))?;
+            for record in records {
+                let fields: Vec<String> = record.iter().map(|value| escape(&field_text(value))).collect();
+                writeln!(out, "{}", fields.join(separator))?;
+            }
+        }
+        Format::Template(template) => {
+            for record in records {
+                writeln!(out, "{}", template.render(headers, record))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes each path followed by a NUL byte, for `xargs -0` and the like.
+pub fn write_nul_delimited<W: Write>(out: &mut W, paths: &[String]) -> io::Result<()> {
+    for path in paths {
+        out.write_all(path.as_bytes())?;
+        out.write_all(b"\0")?;
+    }
+    Ok(())
+}
+
+/// Handles the result of writing the output. A closed pipe, e.g. when
+/// piping into `head`, is not an error.
+pub fn check_output(result: io::Result<()>) {
+    if let Err(error) = result {
+        if error.kind() != io::ErrorKind::BrokenPipe {
+            panic!("Couldn't write the output: {}", error);
+        }
+    }
+}
+
+/// Runs `write` on stdout, see [`check_output`].
+pub fn to_stdout<F>(write: F)
+where
+    F: FnOnce(&mut io::StdoutLock) -> io::Result<()>,
+{
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    check_output(write(&mut out).and_then(|_| out.flush()));
+}
@@ -0,0 +1,337 @@
+//! Boolean tag query language used by `rtag search`.
+//!
+//! A query combines tags with `AND`, `OR`, `NOT` and parentheses, e.g.
+//! `rust AND (paper OR draft) AND NOT archived`. Keywords are case-insensitive,
+//! `NOT` binds tighter than `AND`, which binds tighter than `OR`. Tags containing
+//! whitespace, parentheses or keywords can be written in double quotes.
+//! A hierarchical tag such as `lang/rust` also matches its descendants.
+//! Values of tags can be compared without spaces around the operator, e.g.
+//! `rating>=4` or `due<today`; see [`crate::values`] for the value types.
+//! `note:word` matches items whose note contains `word`, as well as note items
+//! containing it; several words are quoted as in `note:"to read"`.
+//!
+//! Grammar:
+//!
+//! ```text
+//! expr  := and ("OR" and)*
+//! and   := unary ("AND" unary)*
+//! unary := "NOT" unary | atom
+//! atom  := "(" expr ")" | TAG | TAG OP VALUE | "note:" TEXT
+//! OP    := "=" | "!=" | "<" | "<=" | ">" | ">="
+//! ```
+use rusqlite::types::Value;
+use std::fmt;
+
+use crate::kinds::NOTE_PREFIX;
+use crate::rtag_sqlite::{escape_like, tag_subtree_ids_sql, NOTE_ITEM};
+use crate::times::TimeRange;
+use crate::values::TagValue;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn sql(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+/// A comparison of the value of a tag such as `rating>=4`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub tag: String,
+    pub op: CompareOp,
+    pub value: TagValue,
+}
+
+impl Comparison {
+    /// Parses `rating>=4`. Returns `Ok(None)` for words without an operator.
+    pub fn parse(word: &str) -> Result<Option<Comparison>, String> {
+        let start = match word.find(['=', '!', '<', '>']) {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let (tag, rest) = word.split_at(start);
+        let (op, value) = [
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("=", CompareOp::Eq),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ]
+        .iter()
+        .find_map(|(symbol, op)| rest.strip_prefix(symbol).map(|value| (*op, value)))
+        .ok_or_else(|| format!("invalid operator in '{}'", word))?;
+        if tag.is_empty() {
+            return Err(format!("missing tag before the operator in '{}'", word));
+        }
+        if value.is_empty() {
+            return Err(format!("missing value after the operator in '{}'", word));
+        }
+        Ok(Some(Comparison {
+            tag: String::from(tag),
+            op,
+            value: TagValue::parse(value),
+        }))
+    }
+
+    /// Returns a SQL condition comparing `value` and `value_type` columns,
+    /// qualified with `table`, to the value of the comparison.
+    pub fn value_sql(&self, table: &str, params: &mut Vec<Value>) -> String {
+        let types = self.value.comparable_types();
+        params.extend(types.iter().map(|name| Value::Text(String::from(*name))));
+        params.push(self.value.to_sql_value());
+        format!(
+            "{table}.value_type IN ({}) AND {table}.value {} ?",
+            vec!["?"; types.len()].join(", "),
+            self.op.sql(),
+            table = table
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Tag(String),
+    Compare(Comparison),
+    /// Text contained in the note of an item.
+    Note(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Quoted(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push((Token::LParen, pos));
+        } else if c == ')' {
+            chars.next();
+            tokens.push((Token::RParen, pos));
+        } else if c == '"' {
+            chars.next();
+            let mut word = String::new();
+            let mut closed = false;
+            for (_, c) in chars.by_ref() {
+                if c == '"' {
+                    closed = true;
+                    break;
+                }
+                word.push(c);
+            }
+            if !closed {
+                return Err(ParseError {
+                    message: String::from("unterminated quote"),
+                    position: pos,
+                });
+            }
+            tokens.push((Token::Quoted(word), pos));
+        } else {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            let token = match word.to_uppercase().as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                _ => Token::Word(word),
+            };
+            tokens.push((token, pos));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(_, p)| *p).unwrap_or(self.end)
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            message: String::from(message),
+            position: self.position(),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::And) = self.peek() {
+            self.pos += 1;
+            let rhs = self.parse_unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(Token::Not) = self.peek() {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, ParseError> {
+        match self.peek().cloned() {
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                match self.peek() {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(self.error("expected ')'")),
+                }
+            }
+            Some(Token::Word(word)) if word.starts_with(NOTE_PREFIX) => {
+                self.pos += 1;
+                let mut text = String::from(&word[NOTE_PREFIX.len()..]);
+                if text.is_empty() {
+                    match self.peek() {
+                        Some(Token::Quoted(quoted)) => {
+                            text = quoted.clone();
+                            self.pos += 1;
+                        }
+                        _ => return Err(self.error("expected text after 'note:'")),
+                    }
+                }
+                Ok(Expr::Note(text))
+            }
+            Some(Token::Word(word)) => {
+                let comparison = Comparison::parse(&word).map_err(|message| self.error(&message))?;
+                self.pos += 1;
+                Ok(comparison.map_or(Expr::Tag(word), Expr::Compare))
+            }
+            Some(Token::Quoted(word)) => {
+                self.pos += 1;
+                Ok(Expr::Tag(word))
+            }
+            _ => Err(self.error("expected tag or '('")),
+        }
+    }
+}
+
+/// Parses a query string into an expression tree.
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        end: input.len(),
+    };
+    let expr = parser.parse_or()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected token"));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    /// Compiles the expression into a SQL condition on the `items` table.
+    ///
+    /// A tag matches paths tagged with it or any of its descendants, a
+    /// comparison those whose value compares accordingly. Tag names and
+    /// values are never spliced into the SQL; each one is pushed to `params`
+    /// and referenced by a `?` placeholder. Tags and comparisons only match
+    /// associations made within `range`.
+    pub fn to_sql(&self, range: &TimeRange, params: &mut Vec<Value>) -> String {
+        match self {
+            Expr::Tag(tag) => {
+                params.push(Value::Text(tag.clone()));
+                let mut conditions = vec![format!("tag_id IN ({})", tag_subtree_ids_sql(1))];
+                conditions.extend(range.conditions("tagged_at", params));
+                format!("items.id IN (SELECT item_id FROM item_tags WHERE {})", conditions.join(" AND "))
+            }
+            Expr::Compare(comparison) => {
+                params.push(Value::Text(comparison.tag.clone()));
+                let mut conditions = vec![format!("tag_id IN ({})", tag_subtree_ids_sql(1))];
+                conditions.push(comparison.value_sql("item_tags", params));
+                conditions.extend(range.conditions("tagged_at", params));
+                format!("items.id IN (SELECT item_id FROM item_tags WHERE {})", conditions.join(" AND "))
+            }
+            Expr::Note(text) => {
+                let pattern = format!("%{}%", escape_like(text));
+                params.push(Value::Text(pattern.clone()));
+                params.push(Value::Text(format!("{}{}", NOTE_PREFIX, pattern)));
+                format!(
+                    "(items.note LIKE ? ESCAPE '\\' OR (items.type = '{}' AND items.path LIKE ? ESCAPE '\\'))",
+                    NOTE_ITEM
+                )
+            }
+            Expr::Not(inner) => format!("NOT ({})", inner.to_sql(range, params)),
+            Expr::And(lhs, rhs) => format!("({} AND {})", lhs.to_sql(range, params), rhs.to_sql(range, params)),
+            Expr::Or(lhs, rhs) => format!("({} OR {})", lhs.to_sql(range, params), rhs.to_sql(range, params)),
+        }
+    }
+}
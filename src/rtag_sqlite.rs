@@ -1,168 +1,1059 @@
-use rusqlite::{NO_PARAMS, Statement};
-use rusqlite::{Connection, Error, Result};
-use prettytable::{Table, Row, Cell};
-
-static dim_fct_rows: &'static [&'static str] = &["ID", "TAG", "PATH", "TIME_CREATED"];
-
-pub fn create_db_and_initialize_tables() -> Result<Connection, Error> {
-    let conn = Connection::open("rtag.db")?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS dim_tag (
-                id              INTEGER PRIMARY KEY,
-                tag_name VARCHAR UNIQUE,
-                time_created    TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                )",
-        NO_PARAMS,
-    )
-    .unwrap();
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS fct_tag (
-                id  INTEGER,
-                path VARCHAR
-                )",
-        NO_PARAMS,
-    )
-    .unwrap();
+use rusqlite::types::{Value, ValueRef};
+use serde_json::{json, Value as JsonValue};
+use rusqlite::{ffi, params, Connection, Error, OptionalExtension, Result, ToSql, NO_PARAMS};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::fingerprint::Fingerprint;
+use crate::history::in_operation;
+use crate::kinds::Registry;
+use crate::migrations;
+use crate::output::{check_output, write_nul_delimited, write_records, Format};
+use crate::query::{Comparison, Expr};
+use crate::times::TimeRange;
+use crate::values::{split_tag_value, TagValue};
+
+/// Values of `items.type`.
+pub const PATH_ITEM: &str = "path";
+pub const URL_ITEM: &str = "url";
+pub const NOTE_ITEM: &str = "note";
+
+/// Joins every tag with the items tagged with it.
+const TAGGED_ITEMS: &str =
+    "tags JOIN item_tags ON item_tags.tag_id = tags.id JOIN items ON items.id = item_tags.item_id";
+
+/// Fields of the rows of `show`, see [`ShowOptions::columns`]. `tag` is the
+/// tag of the row, shown as `rating=4` if it has a value, `tagged_at` is when
+/// the item was tagged with it and `tags` lists all tags of the item.
+pub const SHOW_FIELDS: &[&str] =
+    &["id", "tag", "path", "time_created", "type", "note", "description", "value", "tagged_at", "tags"];
+
+/// Fields of the rows of `show` with one row per item, see
+/// [`ShowOptions::by_item`].
+pub const ITEM_FIELDS: &[&str] = &["path", "type", "note", "tags"];
+
+/// Columns of [`show_sql`]: the [`SHOW_FIELDS`] up to `tagged_at`, then the
+/// id of the item to look up its tags.
+const SHOW_COLUMNS: &str = "tags.id, tag_name || COALESCE('=' || item_tags.value, ''), path, tags.time_created, \
+                            items.type, items.note, tags.description, item_tags.value, item_tags.tagged_at, items.id";
+
+/// Opens the database at `path` without touching its schema.
+pub fn open_db(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+    Ok(conn)
+}
+
+pub fn create_db_and_initialize_tables(path: &Path) -> Result<Connection, Error> {
+    let conn = open_db(path)?;
+    initialize_tables(&conn)?;
     Ok(conn)
 }
-fn insert_path_tag_to_fct_tag(conn: &Connection, tag_id: i32, path: &str, tag: &str) -> Result<()> {
-    let query = format!("insert into fct_tag (id, path) values ({}, '{}')", tag_id, path);
-    conn.execute(query.as_str(), NO_PARAMS)?;
-    println!("Added path {} to tag {}", path, tag);
+
+/// Brings the schema up to date by applying all pending migrations.
+pub fn initialize_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+    migrations::migrate(conn)?;
+    Ok(())
+}
+
+/// Returns SQL selecting the ids of the tags named by `n` placeholders and
+/// of all their descendants. Names may be aliases of a tag.
+pub(crate) fn tag_subtree_ids_sql(n: usize) -> String {
+    let names = if n == 0 {
+        String::from("SELECT NULL WHERE 0")
+    } else {
+        vec!["SELECT ?"; n].join(" UNION ALL ")
+    };
+    format!(
+        "WITH RECURSIVE names(name) AS ({}),
+                subtree(id) AS (
+                SELECT id FROM tags WHERE tag_name IN names
+                    OR id IN (SELECT tag_id FROM tag_alias WHERE alias IN names)
+                UNION SELECT tags.id FROM tags JOIN subtree ON tags.parent_id = subtree.id
+                ) SELECT id FROM subtree",
+        names
+    )
+}
+
+/// Returns the parent of a hierarchical tag, e.g. `lang/rust` for
+/// `lang/rust/async`, or `None` for a top level tag.
+pub fn parent_tag_name(tag: &str) -> Option<&str> {
+    tag.rsplit_once('/').map(|(parent, _)| parent).filter(|parent| !parent.is_empty())
+}
+
+/// Runs `f` inside a savepoint, so its writes are applied atomically whether
+/// or not the caller already opened a transaction.
+pub fn in_savepoint<T, F>(conn: &Connection, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    conn.execute_batch("SAVEPOINT rtag")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE rtag")?;
+            Ok(value)
+        }
+        Err(error) => {
+            conn.execute_batch("ROLLBACK TO rtag; RELEASE rtag")?;
+            Err(error)
+        }
+    }
+}
+
+/// Returns an error for operations that would break the tag hierarchy or
+/// create invalid tags, reported like the constraint violations raised by the schema.
+fn constraint_error(message: &str) -> Error {
+    Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_CONSTRAINT), Some(String::from(message)))
+}
+
+/// Characters that separate a tag from its value, in `rating=4` and in
+/// comparisons such as `rating>=4`, so tag names cannot contain them.
+const VALUE_SEPARATORS: &[char] = &['=', '!', '<', '>'];
+
+fn check_tag_name(tag: &str) -> Result<()> {
+    if tag.contains(VALUE_SEPARATORS) {
+        return Err(constraint_error("tag names cannot contain =, !, < or >"));
+    }
     Ok(())
 }
 
-fn get_id_of_tag(conn: &Connection, tag_name: &str) -> Result<(i32)> {
-    let select_query = format!("select id from dim_tag where tag_name = '{}'", tag_name);
-    conn.query_row(select_query.as_str(), NO_PARAMS, |row| row.get(0))
+/// Escapes `%`, `_` and the escape character itself for use in a
+/// `LIKE ... ESCAPE '\'` pattern.
+pub(crate) fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Returns the id of the item for `path`, creating it with `item_type` if
+/// necessary.
+fn get_or_create_item(conn: &Connection, path: &str, item_type: &str) -> Result<i32> {
+    conn.prepare_cached("INSERT INTO items (path, type) VALUES (?1, ?2) ON CONFLICT (path) DO NOTHING")?
+        .execute(params![path, item_type])?;
+    conn.prepare_cached("SELECT id FROM items WHERE path = ?1")?
+        .query_row(params![path], |row| row.get(0))
 }
 
-fn check_if_path_tag_exists(conn: &Connection, path: &str, tag: &str) -> Result<i32> {
-    let select_query = format!("select count(*) from dim_tag a join fct_tag b using (id) where path = '{}' and tag_name = '{}'", path, tag);
-    conn.query_row(select_query.as_str(), NO_PARAMS, |row| row.get(0))
+/// Deletes items that are no longer tagged with anything.
+fn prune_items(conn: &Connection) -> Result<usize> {
+    conn.prepare_cached("DELETE FROM items WHERE id NOT IN (SELECT item_id FROM item_tags)")?
+        .execute(NO_PARAMS)
 }
 
+/// Returns the id of a tag, resolving aliases to their canonical tag.
+fn get_id_of_tag(conn: &Connection, tag_name: &str) -> Result<Option<i32>> {
+    conn.prepare_cached(
+        "SELECT id FROM tags WHERE tag_name = ?1
+         UNION ALL SELECT tag_id FROM tag_alias WHERE alias = ?1
+         LIMIT 1",
+    )?
+    .query_row(params![tag_name], |row| row.get(0))
+    .optional()
+}
+
+/// Tags `path` with `tag`, creating the tag if necessary. Aliases are
+/// resolved to their canonical tag. A tag like `rating=4` sets the value of
+/// the association, replacing an earlier one.
 pub fn insert_path(conn: &Connection, path: &str, tag: &str) -> Result<()> {
-    let select_result: Result<i32> =
-        get_id_of_tag(conn, tag);
-    
-    println!("this is insert_path: {:?}", check_if_path_tag_exists(conn, path, tag));
-    // todo: check that no double occurances can happen!!!
-    if let Ok(0) = check_if_path_tag_exists(conn, path, tag) {
-        match select_result {
-            Ok(id) => {
-                insert_path_tag_to_fct_tag(conn, id, path, tag);
+    insert_item(conn, path, PATH_ITEM, tag)
+}
+
+/// Tags the item `path` of type `item_type` with `tag`, see [`insert_path`].
+pub fn insert_item(conn: &Connection, path: &str, item_type: &str, tag: &str) -> Result<()> {
+    in_operation(conn, "tag", json!({ "type": item_type, "paths": [path], "tags": [tag] }), || {
+        let (tag, value) = split_tag_value(tag);
+        let tag_id = match get_id_of_tag(conn, tag)? {
+            Some(id) => id,
+            None => {
+                eprintln!("Couldn't find tag {}. Create new tag", tag);
+                create_new_tag(conn, tag)?
+            }
+        };
+        let item_id = get_or_create_item(conn, path, item_type)?;
+        let sql_value = value.as_ref().map(TagValue::to_sql_value);
+        let value_type = value.as_ref().map(TagValue::type_name);
+        let inserted = conn
+            .prepare_cached(
+                "INSERT OR IGNORE INTO item_tags (item_id, tag_id, value, value_type, tagged_at) \
+                 VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)",
+            )?
+            .execute(params![item_id, tag_id, sql_value, value_type])?;
+        match (inserted, &value) {
+            (0, Some(value)) => {
+                conn.prepare_cached(
+                    "UPDATE item_tags SET value = ?3, value_type = ?4, tagged_at = CURRENT_TIMESTAMP \
+                     WHERE item_id = ?1 AND tag_id = ?2",
+                )?
+                    .execute(params![item_id, tag_id, sql_value, value_type])?;
+                eprintln!("Set {} of {} {} to {}", tag, item_type, path, value);
             }
-            Err(e) => {
-                println!("Couldn't find tag {}. Create new tag", tag);
-                create_new_tag(conn, tag);
-                let tag_id = get_id_of_tag(conn, tag).unwrap();
-                insert_path_tag_to_fct_tag(conn, tag_id, path, tag);
+            (0, None) => {
+                eprintln!("The combination of tag {} and {} {} already exists", tag, item_type, path);
+                return Ok(());
             }
+            (_, Some(value)) => eprintln!("Added {} {} to tag {} with value {}", item_type, path, tag, value),
+            (_, None) => eprintln!("Added {} {} to tag {}", item_type, path, tag),
         }
+        conn.prepare_cached("UPDATE tags SET time_last_used = CURRENT_TIMESTAMP WHERE id = ?1")?
+            .execute(params![tag_id])?;
+        Ok(())
+    })
+}
+
+/// Tags every path with every tag in a single transaction.
+pub fn insert_paths(conn: &Connection, paths: &[String], tags: &[String]) -> Result<()> {
+    insert_items(conn, paths, PATH_ITEM, tags)
+}
+
+/// Tags every item of type `item_type` with every tag in a single transaction.
+pub fn insert_items(conn: &Connection, paths: &[String], item_type: &str, tags: &[String]) -> Result<()> {
+    in_operation(conn, "tag", json!({ "type": item_type, "paths": paths, "tags": tags }), || {
+        for path in paths {
+            for tag in tags {
+                insert_item(conn, path, item_type, tag)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+fn get_or_create_tag(conn: &Connection, tag: &str) -> Result<i32> {
+    match get_id_of_tag(conn, tag)? {
+        Some(id) => Ok(id),
+        None => create_new_tag(conn, tag),
     }
-    else {
-        println!("The combination of tag {} and path {} already exists", tag, path);
-    }
-    
-    Ok(())
 }
 
-pub fn create_new_tag(conn: &Connection, tag: &str) -> Result<()> {
-    let query = format!("insert into dim_tag (tag_name) values ('{}')", tag);
-    let query_str = query.as_str();
-    println!("this is the query str: {}", query_str);
-    conn.execute(query_str, NO_PARAMS).unwrap();
-    Ok(())
+/// Creates a tag and returns its id. Missing ancestors of a hierarchical tag
+/// like `lang/rust/async` are created as well. Fails if the name contains
+/// `=`, `!`, `<` or `>`.
+pub fn create_new_tag(conn: &Connection, tag: &str) -> Result<i32> {
+    in_operation(conn, "create", json!({ "tag": tag }), || {
+        check_tag_name(tag)?;
+        let parent_id = match parent_tag_name(tag) {
+            Some(parent) => Some(get_or_create_tag(conn, parent)?),
+            None => None,
+        };
+        conn.prepare_cached("INSERT INTO tags (tag_name, parent_id) VALUES (?1, ?2)")?
+            .execute(params![tag, parent_id])?;
+        Ok(conn.last_insert_rowid() as i32)
+    })
 }
 
-#[derive(Debug)]
-struct dim_fct_tag {
-    id: i32,
-    tag: String,
-    path: String,
-    time_created: String,
+/// Makes `alias` resolve to `tag` when tagging, showing and searching. The
+/// tag is created if it doesn't exist. Fails if `alias` is itself a tag or
+/// isn't a valid tag name.
+pub fn add_alias(conn: &Connection, alias: &str, tag: &str) -> Result<()> {
+    in_operation(conn, "alias", json!({ "alias": alias, "tag": tag }), || {
+        check_tag_name(alias)?;
+        let tag_id = get_or_create_tag(conn, tag)?;
+        conn.prepare_cached("INSERT OR REPLACE INTO tag_alias (alias, tag_id) VALUES (?1, ?2)")?
+            .execute(params![alias, tag_id])?;
+        Ok(())
+    })
 }
 
-#[derive(Debug)]
-struct dim_tag {
-    id: i32,
-    tag_name: String,
-    time_created: String,
+/// Removes an alias. Returns `false` if it didn't exist.
+pub fn remove_alias(conn: &Connection, alias: &str) -> Result<bool> {
+    in_operation(conn, "unalias", json!({ "alias": alias }), || {
+        let deleted = conn.prepare_cached("DELETE FROM tag_alias WHERE alias = ?1")?
+            .execute(params![alias])?;
+        Ok(deleted > 0)
+    })
 }
 
-#[derive(Debug)]
-struct id {
-    id: i32,
+/// Returns all `(alias, tag_name)` pairs ordered by alias.
+pub fn get_aliases(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT alias, tag_name FROM tag_alias JOIN tags ON tag_alias.tag_id = tags.id ORDER BY alias",
+    )?;
+    let aliases = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+    aliases.collect()
 }
 
-pub fn show_all(conn: &Connection) -> Result<()> {
-    let sql = "SELECT id, tag_name, path, time_created FROM dim_tag join fct_tag using (id)";
-    show_sql(&conn, sql, dim_fct_rows)
+#[derive(Debug)]
+pub struct DimTag {
+    pub id: i32,
+    pub tag_name: String,
+    pub parent_id: Option<i32>,
+    pub time_created: String,
 }
 
-pub fn show_sql(conn: &Connection, sql_statement: &str, row_headers: &[&str]) -> Result<()> {
-    let mut table = Table::new();
-    let stmt = conn.prepare(sql_statement);
-    let mut stmt_un = stmt.unwrap();
-    let table_iter = stmt_un.query_map(NO_PARAMS, |row| {
-        Ok(dim_fct_tag {
+/// Returns all tags ordered by name.
+pub fn get_tags(conn: &Connection) -> Result<Vec<DimTag>> {
+    let mut stmt = conn.prepare_cached("SELECT id, tag_name, parent_id, time_created FROM tags ORDER BY tag_name")?;
+    let tags = stmt.query_map(NO_PARAMS, |row| {
+        Ok(DimTag {
             id: row.get(0)?,
-            tag: row.get(1)?,
-            path: row.get(2)?,
+            tag_name: row.get(1)?,
+            parent_id: row.get(2)?,
             time_created: row.get(3)?,
         })
-    });
-    table.add_row(Row::from(row_headers));
-    for row in table_iter.unwrap().into_iter() {
-        let row_un = row.unwrap();
-        table.add_row(row![row_un.id, row_un.tag, row_un.path, row_un.time_created]);
+    })?;
+    tags.collect()
+}
+
+/// A tag with the number of items tagged with it.
+#[derive(Debug)]
+pub struct TagUsage {
+    pub tag_name: String,
+    pub count: i64,
+    pub time_created: String,
+    /// When the tag was last applied to an item, unknown for tags that were
+    /// last applied before this was recorded.
+    pub time_last_used: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagSort {
+    /// Alphabetically.
+    Name,
+    /// Most used first.
+    Count,
+    /// Newest first.
+    Created,
+    /// Most recently used first.
+    Used,
+}
+
+pub const TAG_SORT_NAMES: &[&str] = &["name", "count", "created", "used"];
+
+impl FromStr for TagSort {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<TagSort, String> {
+        match name {
+            "name" => Ok(TagSort::Name),
+            "count" => Ok(TagSort::Count),
+            "created" => Ok(TagSort::Created),
+            "used" => Ok(TagSort::Used),
+            _ => Err(format!("unknown sort order {}, expected one of {}", name, TAG_SORT_NAMES.join(", "))),
+        }
     }
-    table.printstd();
+}
 
+/// Returns the tags with at least `min_count` items, or only the tags
+/// without items if `unused` is set. Counts don't include the items of
+/// descendant tags.
+pub fn get_tag_usage(conn: &Connection, sort: TagSort, min_count: i64, unused: bool) -> Result<Vec<TagUsage>> {
+    let order = match sort {
+        TagSort::Name => "tag_name",
+        TagSort::Count => "count DESC, tag_name",
+        TagSort::Created => "time_created DESC, tag_name",
+        TagSort::Used => "time_last_used IS NULL, time_last_used DESC, tag_name",
+    };
+    let sql = format!(
+        "SELECT tag_name, count(item_tags.item_id) AS count, time_created, time_last_used \
+         FROM tags LEFT JOIN item_tags ON item_tags.tag_id = tags.id GROUP BY tags.id \
+         HAVING {} ORDER BY {}",
+        if unused { "count = 0" } else { "count >= ?1" },
+        order
+    );
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let params: &[&dyn ToSql] = if unused { &[] } else { &[&min_count] };
+    let tags = stmt.query_map(params, |row| {
+        Ok(TagUsage {
+            tag_name: row.get(0)?,
+            count: row.get(1)?,
+            time_created: row.get(2)?,
+            time_last_used: row.get(3)?,
+        })
+    })?;
+    tags.collect()
+}
+
+/// Returns the ids of all descendants of a tag, excluding the tag itself.
+pub fn get_descendant_ids(conn: &Connection, id: i32) -> Result<Vec<i32>> {
+    let mut stmt = conn.prepare_cached(
+        "WITH RECURSIVE subtree(id) AS (
+                SELECT id FROM tags WHERE parent_id = ?1
+                UNION SELECT tags.id FROM tags JOIN subtree ON tags.parent_id = subtree.id
+                ) SELECT id FROM subtree",
+    )?;
+    let ids = stmt.query_map(params![id], |row| row.get(0))?;
+    ids.collect()
+}
+
+/// Renders the tag hierarchy as an indented tree, one line per tag.
+pub fn tag_tree_lines(tags: &[DimTag]) -> Vec<String> {
+    fn add_children(tags: &[DimTag], parent: Option<&DimTag>, prefix: &str, lines: &mut Vec<String>) {
+        let parent_id = parent.map(|parent| parent.id);
+        let children: Vec<&DimTag> = tags.iter().filter(|tag| tag.parent_id == parent_id).collect();
+        for (i, tag) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            // children are shown relative to their parent, orphans with their full name
+            let name = parent
+                .and_then(|parent| tag.tag_name.strip_prefix(parent.tag_name.as_str()))
+                .and_then(|name| name.strip_prefix('/'))
+                .unwrap_or(&tag.tag_name);
+            let (branch, indent) = match (parent, last) {
+                (None, _) => ("", ""),
+                (Some(_), false) => ("├── ", "│   "),
+                (Some(_), true) => ("└── ", "    "),
+            };
+            lines.push(format!("{}{}{}", prefix, branch, name));
+            add_children(tags, Some(tag), format!("{}{}", prefix, indent).as_str(), lines);
+        }
+    }
+    let mut lines = Vec::new();
+    add_children(tags, None, "", &mut lines);
+    lines
+}
+
+pub fn show_tag_tree(conn: &Connection) -> Result<()> {
+    for line in tag_tree_lines(&get_tags(conn)?) {
+        println!("{}", line);
+    }
     Ok(())
 }
 
-pub fn show_tags(conn: &Connection, tags: String) -> Result<()> {
-    println!("Before creating prepare");
-    let sql = format!("SELECT id, tag_name, path, time_created FROM dim_tag join fct_tag using (id) where tag_name in ({})", tags);
-    show_sql(&conn, sql.as_str(), dim_fct_rows)
+/// How `show` displays its rows.
+pub struct ShowOptions<'a> {
+    /// Displays the items by their kind in tables.
+    pub kinds: &'a Registry,
+    /// Adds the notes of the items and the descriptions of the tags to the
+    /// default columns.
+    pub long: bool,
+    /// The [`SHOW_FIELDS`] to show, instead of the default columns.
+    pub columns: Option<Vec<String>>,
+    /// Shows one row per item with all its tags instead of a row per tag of
+    /// an item. Rows only have the [`ITEM_FIELDS`].
+    pub by_item: bool,
+    pub format: Format,
+    /// Only writes the paths, each followed by a NUL byte.
+    pub nul_delimited: bool,
+    /// Only shows the tags applied within this range.
+    pub tagged: TimeRange,
+    /// The order of the rows, unspecified if `None`.
+    pub sort: Option<ShowSort>,
 }
 
-pub fn show_paths(conn: &Connection, paths: Vec<String>) -> Result<()> {
-    println!("Before creating prepare in show_paths");
-    let end_str = paths.join("%' or path like '%");
-    let mut paths_query = String::from("path like '%");
-    paths_query.push_str(end_str.as_str());
-    paths_query.push_str("%'");
-    println!("this is the second paths_query: {}", paths_query);
-    let sql = format!("SELECT id, tag_name, path, time_created FROM dim_tag join fct_tag using (id) where {}", paths_query);
-    show_sql(&conn, sql.as_str(), dim_fct_rows)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShowSort {
+    Path,
+    Tag,
+    /// Most recently tagged first.
+    TaggedAt,
 }
 
-pub fn delete_by_id(conn: &Connection, ids: String) -> Result<()> {
-    println!("Delete the following ids: {:?}", ids);
-    let query_fct_tag = format!("delete from fct_tag where id in ({})", ids);
-    let query_dim_tag = format!("delete from dim_tag where id in ({})", ids);
-    conn.execute(query_fct_tag.as_str(), NO_PARAMS)?;
-    conn.execute(query_dim_tag.as_str(), NO_PARAMS)?;
+pub const SHOW_SORT_NAMES: &[&str] = &["path", "tag", "tagged_at"];
+
+impl FromStr for ShowSort {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<ShowSort, String> {
+        match name {
+            "path" => Ok(ShowSort::Path),
+            "tag" => Ok(ShowSort::Tag),
+            "tagged_at" => Ok(ShowSort::TaggedAt),
+            _ => Err(format!("unknown sort order {}, expected one of {}", name, SHOW_SORT_NAMES.join(", "))),
+        }
+    }
+}
+
+impl ShowOptions<'_> {
+    /// Returns the `WHERE` and `ORDER BY` clauses selecting the rows that
+    /// match all `conditions` and were tagged within [`ShowOptions::tagged`].
+    fn filter_sql(&self, mut conditions: Vec<String>, params: &mut Vec<Value>) -> String {
+        conditions.extend(self.tagged.conditions("item_tags.tagged_at", params));
+        let mut sql = String::new();
+        if !conditions.is_empty() {
+            sql = format!(" WHERE {}", conditions.join(" AND "));
+        }
+        match self.sort {
+            Some(ShowSort::Path) => sql.push_str(" ORDER BY path, tag_name"),
+            Some(ShowSort::Tag) => sql.push_str(" ORDER BY tag_name, path"),
+            Some(ShowSort::TaggedAt) => {
+                sql.push_str(" ORDER BY item_tags.tagged_at IS NULL, item_tags.tagged_at DESC, path, tag_name")
+            }
+            None => {}
+        }
+        sql
+    }
+
+    /// Returns the fields the rows can have.
+    pub fn known_fields(&self) -> &'static [&'static str] {
+        if self.by_item {
+            ITEM_FIELDS
+        } else {
+            SHOW_FIELDS
+        }
+    }
+
+    /// Returns the fields to show. Templates can use all known fields.
+    fn fields(&self) -> Vec<&str> {
+        if let Format::Template(_) = self.format {
+            return self.known_fields().to_vec();
+        }
+        if let Some(columns) = &self.columns {
+            return columns.iter().map(String::as_str).collect();
+        }
+        let mut fields = match self.by_item {
+            true => vec!["path", "tags"],
+            false => vec!["id", "tag", "path", "time_created"],
+        };
+        if self.format != Format::Table {
+            fields.push("type");
+        }
+        if self.long {
+            fields.push("note");
+            if !self.by_item {
+                fields.push("description");
+            }
+        }
+        fields
+    }
+}
+
+fn json_of(value: ValueRef) -> JsonValue {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => JsonValue::Null,
+        ValueRef::Integer(int) => int.into(),
+        ValueRef::Real(float) => float.into(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+    }
+}
+
+/// Returns the tags of an item, sorted by name, with their values.
+pub fn get_tags_of_item(conn: &Connection, item_id: i64) -> Result<Vec<String>> {
+    conn.prepare_cached(
+        "SELECT tag_name || COALESCE('=' || item_tags.value, '') FROM item_tags JOIN tags ON tags.id = item_tags.tag_id \
+         WHERE item_tags.item_id = ?1 ORDER BY tag_name",
+    )?
+    .query_map(params![item_id], |row| row.get(0))?
+    .collect()
+}
+
+/// Returns the tags of the item stored as `path`, or `None` if there is no
+/// such item.
+pub fn get_tags_of_path(conn: &Connection, path: &str) -> Result<Option<Vec<String>>> {
+    let item_id: Option<i64> = conn
+        .prepare_cached("SELECT id FROM items WHERE path = ?1")?
+        .query_row(params![path], |row| row.get(0))
+        .optional()?;
+    item_id.map(|item_id| get_tags_of_item(conn, item_id)).transpose()
+}
+
+pub fn show_all<W: Write>(conn: &Connection, out: &mut W, options: &ShowOptions) -> Result<()> {
+    let mut params: Vec<Value> = Vec::new();
+    let filter = options.filter_sql(Vec::new(), &mut params);
+    let sql = format!("SELECT {} FROM {}{}", SHOW_COLUMNS, TAGGED_ITEMS, filter);
+    show_sql(conn, out, options, sql.as_str(), &params)
+}
+
+/// Writes the rows of `sql_statement`, which selects the [`SHOW_COLUMNS`], to
+/// `out`. Tables show items as displayed by their kind, the other formats
+/// show the stored path.
+pub fn show_sql<W, P>(
+    conn: &Connection,
+    out: &mut W,
+    options: &ShowOptions,
+    sql_statement: &str,
+    params: P,
+) -> Result<()>
+where
+    W: Write,
+    P: IntoIterator,
+    P::Item: ToSql,
+{
+    let fields = options.fields();
+    let with_tags = fields.contains(&"tags");
+    let field_index = |name| SHOW_FIELDS.iter().position(|field| *field == name).unwrap();
+    let (path_index, type_index) = (field_index("path"), field_index("type"));
+    let indexes: Vec<usize> = fields.iter().map(|field| field_index(field)).collect();
+
+    let mut stmt = conn.prepare_cached(sql_statement)?;
+    let mut rows = stmt.query(params)?;
+    let mut paths: Vec<String> = Vec::new();
+    let mut seen_paths = HashSet::new();
+    let mut items = HashSet::new();
+    let mut records: Vec<Vec<JsonValue>> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let item_id: i64 = row.get(SHOW_FIELDS.len() - 1)?;
+        if options.by_item && !items.insert(item_id) {
+            continue;
+        }
+        let path: String = row.get(path_index)?;
+        if options.nul_delimited {
+            if seen_paths.insert(path.clone()) {
+                paths.push(path);
+            }
+            continue;
+        }
+        let mut values: Vec<JsonValue> =
+            (0..SHOW_FIELDS.len() - 1).map(|i| row.get_raw_checked(i).map(json_of)).collect::<Result<_>>()?;
+        values.push(match with_tags {
+            true => get_tags_of_item(conn, item_id)?.into(),
+            false => JsonValue::Null,
+        });
+        if options.format == Format::Table {
+            let item_type: String = row.get(type_index)?;
+            values[path_index] = options.kinds.display(&item_type, &path).into();
+        }
+        records.push(indexes.iter().map(|&i| values[i].clone()).collect());
+    }
+
+    if options.nul_delimited {
+        check_output(write_nul_delimited(out, &paths).and_then(|_| out.flush()));
+        return Ok(());
+    }
+    let headers: Vec<String> = fields.iter().map(|field| field.to_uppercase()).collect();
+    let headers: Vec<&str> = headers.iter().map(String::as_str).collect();
+    check_output(write_records(out, &options.format, &headers, &records).and_then(|_| out.flush()));
     Ok(())
 }
 
-pub fn delete_by_tag(conn: &Connection, tags: Vec<String>) -> Result<()> {
-    let tags_str = tags.join(",");
-    let query_get_ids = format!("select id from dim_tag where tag_name in ({})", tags_str);
-    let stmt = conn.prepare(query_get_ids.as_str());
-    let mut stmt_un = stmt.unwrap();
-    let table_iter = stmt_un.query_map(NO_PARAMS, |row| {
-        Ok(id {
-            id: row.get(0)?
-        })
-    });
-    let ids = table_iter.unwrap().into_iter().map(|row| row.unwrap().id.to_string()).collect::<Vec<String>>().join(",");
-    delete_by_id(&conn, ids);
+/// Shows all paths tagged with one of `tags` or one of their descendants.
+/// An entry like `rating>=4` only shows the paths whose value matches; an
+/// invalid comparison like `rating>` is an error.
+pub fn show_tags<W: Write>(conn: &Connection, out: &mut W, options: &ShowOptions, tags: &[String]) -> Result<()> {
+    let mut params: Vec<Value> = Vec::new();
+    let mut conditions: Vec<String> = Vec::new();
+    for tag in tags {
+        match Comparison::parse(tag).map_err(|error| Error::ToSqlConversionFailure(error.into()))? {
+            Some(comparison) => {
+                params.push(Value::Text(comparison.tag.clone()));
+                let subtree = tag_subtree_ids_sql(1);
+                conditions.push(format!(
+                    "(tags.id IN ({}) AND {})",
+                    subtree,
+                    comparison.value_sql("item_tags", &mut params)
+                ));
+            }
+            None => {
+                params.push(Value::Text(tag.clone()));
+                conditions.push(format!("tags.id IN ({})", tag_subtree_ids_sql(1)));
+            }
+        }
+    }
+    let filter = options.filter_sql(vec![format!("({})", conditions.join(" OR "))], &mut params);
+    let sql = format!("SELECT {} FROM {}{}", SHOW_COLUMNS, TAGGED_ITEMS, filter);
+    show_sql(conn, out, options, sql.as_str(), &params)
+}
+
+pub fn show_paths<W: Write>(conn: &Connection, out: &mut W, options: &ShowOptions, paths: &[String]) -> Result<()> {
+    let paths_query = vec!["path LIKE '%' || ? || '%' ESCAPE '\\'"; paths.len()].join(" OR ");
+    let mut params: Vec<Value> = paths.iter().map(|p| Value::Text(escape_like(p))).collect();
+    let filter = options.filter_sql(vec![format!("({})", paths_query)], &mut params);
+    let sql = format!("SELECT {} FROM {}{}", SHOW_COLUMNS, TAGGED_ITEMS, filter);
+    show_sql(conn, out, options, sql.as_str(), &params)
+}
+
+fn get_tag_name(conn: &Connection, id: i32) -> Result<String> {
+    conn.prepare_cached("SELECT tag_name FROM tags WHERE id = ?1")?
+        .query_row(params![id], |row| row.get(0))
+}
+
+fn get_child_ids(conn: &Connection, id: i32) -> Result<Vec<i32>> {
+    let mut stmt = conn.prepare_cached("SELECT id FROM tags WHERE parent_id = ?1")?;
+    let ids = stmt.query_map(params![id], |row| row.get(0))?;
+    ids.collect()
+}
+
+/// Replaces the `old` prefix of the names of all descendants of a tag, e.g.
+/// `lang/rust` becomes `language/rust` when `lang` is renamed to `language`.
+fn rename_descendants(conn: &Connection, id: i32, old: &str, new: &str) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE tags SET tag_name = ?3 || substr(tag_name, length(?2) + 1)
+         WHERE id = ?1 AND substr(tag_name, 1, length(?2) + 1) = ?2 || '/'",
+    )?;
+    for descendant in get_descendant_ids(conn, id)? {
+        stmt.execute(params![descendant, old, new])?;
+    }
+    Ok(())
+}
+
+/// Renames a tag. With `cascade`, children are renamed along with it, e.g.
+/// `lang/rust` becomes `language/rust`. Otherwise they keep their names and
+/// are moved to a new tag with the old name. Returns `false` if `old` doesn't
+/// exist.
+pub fn rename_tag(conn: &Connection, old: &str, new: &str, cascade: bool) -> Result<bool> {
+    in_operation(conn, "rename", json!({ "tag": old, "new_name": new, "cascade": cascade }), || {
+        let id = match get_id_of_tag(conn, old)? {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+        check_tag_name(new)?;
+        let old = get_tag_name(conn, id)?;
+        if new.starts_with(format!("{}/", old).as_str()) {
+            return Err(constraint_error("a tag cannot be renamed to one of its descendants"));
+        }
+        let parent_id = match parent_tag_name(new) {
+            Some(parent) => Some(get_or_create_tag(conn, parent)?),
+            None => None,
+        };
+        // renaming a tag to one of its own aliases replaces the alias
+        conn.prepare_cached("DELETE FROM tag_alias WHERE alias = ?1 AND tag_id = ?2")?
+            .execute(params![new, id])?;
+        conn.prepare_cached("UPDATE tags SET tag_name = ?2, parent_id = ?3 WHERE id = ?1")?
+            .execute(params![id, new, parent_id])?;
+        let children = get_child_ids(conn, id)?;
+        if cascade {
+            rename_descendants(conn, id, old.as_str(), new)?;
+        } else if !children.is_empty() {
+            let placeholder_id = create_new_tag(conn, old.as_str())?;
+            let mut reparent = conn.prepare_cached("UPDATE tags SET parent_id = ?2 WHERE id = ?1")?;
+            for child in children {
+                reparent.execute(params![child, placeholder_id])?;
+            }
+        }
+        Ok(true)
+    })
+}
+
+/// Moves everything of tag `src_id` to `dst_id` and deletes `src_id`.
+fn merge_tag_into(conn: &Connection, src_id: i32, dst_id: i32) -> Result<()> {
+    let src_name = get_tag_name(conn, src_id)?;
+    let dst_name = get_tag_name(conn, dst_id)?;
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO item_tags (item_id, tag_id, value, value_type, tagged_at)
+         SELECT item_id, ?2, value, value_type, tagged_at FROM item_tags WHERE tag_id = ?1",
+    )?
+        .execute(params![src_id, dst_id])?;
+    conn.prepare_cached("UPDATE tag_alias SET tag_id = ?2 WHERE tag_id = ?1")?
+        .execute(params![src_id, dst_id])?;
+    // children move along: `ml/deep` becomes `machine-learning/deep`, merging
+    // with an existing tag of that name
+    for child_id in get_child_ids(conn, src_id)? {
+        let child_name = get_tag_name(conn, child_id)?;
+        let suffix = child_name.rsplit('/').next().unwrap_or(&child_name);
+        let new_name = format!("{}/{}", dst_name, suffix);
+        match get_id_of_tag(conn, new_name.as_str())? {
+            Some(existing_id) => merge_tag_into(conn, child_id, existing_id)?,
+            None => {
+                conn.prepare_cached("UPDATE tags SET tag_name = ?2, parent_id = ?3 WHERE id = ?1")?
+                    .execute(params![child_id, new_name, dst_id])?;
+                rename_descendants(conn, child_id, child_name.as_str(), new_name.as_str())?;
+            }
+        }
+    }
+    conn.prepare_cached("DELETE FROM tags WHERE id = ?1")?.execute(params![src_id])?;
+    // the old name keeps working as an alias
+    conn.prepare_cached("INSERT INTO tag_alias (alias, tag_id) VALUES (?1, ?2)")?
+        .execute(params![src_name, dst_id])?;
+    Ok(())
+}
+
+/// Merges the `sources` tags into `dst` in a single transaction. All item
+/// associations move to `dst` without creating duplicates, and the source
+/// names become aliases of `dst`. `dst` is created if it doesn't exist.
+/// Returns the names of the sources that didn't exist.
+pub fn merge_tags(conn: &Connection, sources: &[String], dst: &str) -> Result<Vec<String>> {
+    in_operation(conn, "merge", json!({ "tags": sources, "into": dst }), || {
+        let dst_id = get_or_create_tag(conn, dst)?;
+        let mut missing = Vec::new();
+        for src in sources {
+            match get_id_of_tag(conn, src)? {
+                Some(src_id) if get_descendant_ids(conn, src_id)?.contains(&dst_id) => {
+                    return Err(constraint_error("a tag cannot be merged into one of its descendants"));
+                }
+                Some(src_id) if src_id != dst_id => merge_tag_into(conn, src_id, dst_id)?,
+                Some(_) => {}
+                None => missing.push(src.clone()),
+            }
+        }
+        Ok(missing)
+    })
+}
+
+/// Removes `tag` from `path` and keeps the tag itself. Returns the number of
+/// removed associations.
+pub fn untag_path(conn: &Connection, tag: &str, path: &str) -> Result<usize> {
+    in_operation(conn, "untag", json!({ "tag": tag, "path": path }), || {
+        let tag_id = match get_id_of_tag(conn, tag)? {
+            Some(tag_id) => tag_id,
+            None => return Ok(0),
+        };
+        let deleted = conn
+            .prepare_cached("DELETE FROM item_tags WHERE tag_id = ?1 AND item_id IN (SELECT id FROM items WHERE path = ?2)")?
+            .execute(params![tag_id, path])?;
+        prune_items(conn)?;
+        Ok(deleted)
+    })
+}
+
+/// Removes `path` from all of its tags and keeps the tags themselves. Returns
+/// the number of removed associations.
+pub fn forget_path(conn: &Connection, path: &str) -> Result<usize> {
+    in_operation(conn, "forget", json!({ "path": path }), || {
+        let deleted = conn
+            .prepare_cached("DELETE FROM item_tags WHERE item_id IN (SELECT id FROM items WHERE path = ?1)")?
+            .execute(params![path])?;
+        prune_items(conn)?;
+        Ok(deleted)
+    })
+}
+
+/// Records the fingerprint of the file at `path`.
+pub fn set_fingerprint(conn: &Connection, path: &str, fingerprint: &Fingerprint) -> Result<()> {
+    conn.prepare_cached(
+        "UPDATE items SET dev = ?2, inode = ?3, size = ?4, mtime = ?5, content_hash = ?6,
+         time_updated = CURRENT_TIMESTAMP WHERE path = ?1",
+    )?
+    .execute(params![
+        path,
+        fingerprint.dev,
+        fingerprint.inode,
+        fingerprint.size,
+        fingerprint.mtime,
+        fingerprint.content_hash
+    ])?;
     Ok(())
+}
+
+/// Returns the paths and types of all items ordered by path.
+pub fn get_items(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare_cached("SELECT path, type FROM items ORDER BY path")?;
+    let items = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+    items.collect()
+}
+
+/// Returns the paths of all path items ordered by path.
+pub fn get_item_paths(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare_cached("SELECT path FROM items WHERE type = 'path' ORDER BY path")?;
+    let paths = stmt.query_map(NO_PARAMS, |row| row.get(0))?;
+    paths.collect()
+}
+
+/// Returns all path items with their fingerprint, if one was recorded,
+/// ordered by path.
+pub fn get_path_items(conn: &Connection) -> Result<Vec<(String, Option<Fingerprint>)>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT path, dev, inode, size, mtime, content_hash FROM items WHERE type = 'path' ORDER BY path",
+    )?;
+    let items = stmt.query_map(NO_PARAMS, |row| {
+        let inode: Option<i64> = row.get(2)?;
+        let fingerprint = match inode {
+            Some(inode) => Some(Fingerprint {
+                dev: row.get(1)?,
+                inode,
+                size: row.get(3)?,
+                mtime: row.get(4)?,
+                content_hash: row.get(5)?,
+            }),
+            None => None,
+        };
+        Ok((row.get(0)?, fingerprint))
+    })?;
+    items.collect()
+}
+
+/// Returns all path items with a recorded fingerprint, ordered by path.
+pub fn get_fingerprints(conn: &Connection) -> Result<Vec<(String, Fingerprint)>> {
+    Ok(get_path_items(conn)?
+        .into_iter()
+        .filter_map(|(path, fingerprint)| fingerprint.map(|fingerprint| (path, fingerprint)))
+        .collect())
+}
+
+/// Moves the item at `old` to `new` together with its tags and records its
+/// new fingerprint. Returns `false` if there is no item at `old`.
+pub fn move_item(conn: &Connection, old: &str, new: &str, fingerprint: &Fingerprint) -> Result<bool> {
+    in_operation(conn, "move", json!({ "from": old, "to": new }), || {
+        let moved = conn.prepare_cached("UPDATE items SET path = ?2 WHERE path = ?1")?
+            .execute(params![old, new])?;
+        set_fingerprint(conn, new, fingerprint)?;
+        Ok(moved > 0)
+    })
+}
+
+/// Condition on `path` selecting the item at `?1` and all items below it.
+/// Unlike `LIKE`, it is case-sensitive.
+const AT_OR_BELOW: &str = "(path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/')";
+
+/// Returns the ids and paths of the item at `path` and of all items below it.
+fn get_items_at_or_below(conn: &Connection, path: &str) -> Result<Vec<(i32, String)>> {
+    let mut stmt = conn.prepare_cached(&format!("SELECT id, path FROM items WHERE {}", AT_OR_BELOW))?;
+    let items = stmt.query_map(params![path], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    items.collect()
+}
+
+/// Follows a rename of `old` to `new`, including the items below `old` if it
+/// is a directory. If an item already exists at the new path, the tags are
+/// merged into it. Returns the number of moved items.
+pub fn move_path(conn: &Connection, old: &str, new: &str) -> Result<usize> {
+    let items = get_items_at_or_below(conn, old)?;
+    if items.is_empty() {
+        return Ok(0);
+    }
+    in_operation(conn, "move", json!({ "from": old, "to": new }), || {
+        for (id, path) in &items {
+            let new_path = format!("{}{}", new, &path[old.len()..]);
+            let existing: Option<i32> = conn.prepare_cached("SELECT id FROM items WHERE path = ?1")?
+                .query_row(params![new_path], |row| row.get(0))
+                .optional()?;
+            match existing {
+                Some(existing) => {
+                    conn.prepare_cached(
+                        "INSERT OR IGNORE INTO item_tags (item_id, tag_id, value, value_type, tagged_at)
+                         SELECT ?2, tag_id, value, value_type, tagged_at FROM item_tags WHERE item_id = ?1",
+                    )?
+                    .execute(params![id, existing])?;
+                    conn.prepare_cached("DELETE FROM items WHERE id = ?1")?.execute(params![id])?;
+                    conn.prepare_cached("UPDATE items SET missing_since = NULL WHERE id = ?1")?
+                        .execute(params![existing])?;
+                }
+                None => {
+                    conn.prepare_cached(
+                        "UPDATE items SET path = ?2, missing_since = NULL, time_updated = CURRENT_TIMESTAMP WHERE id = ?1",
+                    )?
+                    .execute(params![id, new_path])?;
+                }
+            }
+        }
+        Ok(items.len())
+    })
+}
+
+/// Marks the item at `path` and all items below it as missing. Items that
+/// are already missing keep their original time. Returns the number of
+/// newly missing items.
+pub fn mark_missing(conn: &Connection, path: &str) -> Result<usize> {
+    // without anything to mark nothing is written, so that watching the
+    // directory of the database doesn't react to its own writes
+    let present: i64 = conn
+        .prepare_cached(&format!("SELECT count(*) FROM items WHERE {} AND missing_since IS NULL", AT_OR_BELOW))?
+        .query_row(params![path], |row| row.get(0))?;
+    if present == 0 {
+        return Ok(0);
+    }
+    in_operation(conn, "missing", json!({ "path": path }), || {
+        conn.prepare_cached(&format!(
+            "UPDATE items SET missing_since = CURRENT_TIMESTAMP WHERE {} AND missing_since IS NULL",
+            AT_OR_BELOW
+        ))?
+        .execute(params![path])
+    })
+}
+
+/// Clears the missing mark of the item at `path`. Returns the number of
+/// items that were missing.
+pub fn mark_present(conn: &Connection, path: &str) -> Result<usize> {
+    let missing: bool = conn
+        .prepare_cached("SELECT EXISTS (SELECT 1 FROM items WHERE path = ?1 AND missing_since IS NOT NULL)")?
+        .query_row(params![path], |row| row.get(0))?;
+    if !missing {
+        return Ok(0);
+    }
+    in_operation(conn, "present", json!({ "path": path }), || {
+        conn.prepare_cached("UPDATE items SET missing_since = NULL WHERE path = ?1 AND missing_since IS NOT NULL")?
+            .execute(params![path])
+    })
+}
 
-}
\ No newline at end of file
+/// Deletes tags and their associations. Children of a deleted tag keep their
+/// names under a new, empty tag with the deleted tag's name; pass their ids
+/// as well to delete them too. Returns the number of deleted tags.
+pub fn delete_by_id(conn: &Connection, ids: &[i32]) -> Result<usize> {
+    in_operation(conn, "delete", json!({ "ids": ids }), || {
+        let mut detach_children = conn.prepare_cached("UPDATE tags SET parent_id = NULL WHERE parent_id = ?1")?;
+        // associations and aliases are removed by ON DELETE CASCADE
+        let mut delete_tag = conn.prepare_cached("DELETE FROM tags WHERE id = ?1")?;
+        let mut reparent = conn.prepare_cached("UPDATE tags SET parent_id = ?2 WHERE id = ?1")?;
+        let mut deleted = 0;
+        for id in ids {
+            let mut children = get_child_ids(conn, *id)?;
+            children.retain(|child| !ids.contains(child));
+            let name = if children.is_empty() { None } else { Some(get_tag_name(conn, *id)?) };
+            detach_children.execute(params![id])?;
+            deleted += delete_tag.execute(params![id])?;
+            if let Some(name) = name {
+                let placeholder_id = create_new_tag(conn, &name)?;
+                for child in children {
+                    reparent.execute(params![child, placeholder_id])?;
+                }
+            }
+        }
+        prune_items(conn)?;
+        Ok(deleted)
+    })
+}
+
+pub fn delete_by_tag(conn: &Connection, tags: &[String]) -> Result<usize> {
+    in_operation(conn, "delete", json!({ "tags": tags }), || {
+        delete_by_id(conn, &get_ids_of_tags(conn, tags)?)
+    })
+}
+
+/// Returns the ids of the given tags or aliases, skipping names that don't exist.
+pub fn get_ids_of_tags(conn: &Connection, tags: &[String]) -> Result<Vec<i32>> {
+    let mut ids = Vec::new();
+    for tag in tags {
+        if let Some(id) = get_id_of_tag(conn, tag)? {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Sets the note of the item at `path`, or removes it if `note` is `None`.
+/// Returns `false` if there is no such item.
+pub fn set_item_note(conn: &Connection, path: &str, note: Option<&str>) -> Result<bool> {
+    in_operation(conn, "note", json!({ "path": path, "note": note }), || {
+        let updated = conn.prepare_cached("UPDATE items SET note = ?2, time_updated = CURRENT_TIMESTAMP WHERE path = ?1")?
+            .execute(params![path, note])?;
+        Ok(updated > 0)
+    })
+}
+
+/// Returns the note of the item at `path`. Returns `None` if there is no such
+/// item and `Some(None)` if it has no note.
+pub fn get_item_note(conn: &Connection, path: &str) -> Result<Option<Option<String>>> {
+    conn.prepare_cached("SELECT note FROM items WHERE path = ?1")?
+        .query_row(params![path], |row| row.get(0))
+        .optional()
+}
+
+/// Sets the description of a tag or the tag of an alias, or removes it if
+/// `description` is `None`. Returns `false` if there is no such tag.
+pub fn set_tag_description(conn: &Connection, tag: &str, description: Option<&str>) -> Result<bool> {
+    in_operation(conn, "describe", json!({ "tag": tag, "description": description }), || {
+        let tag_id = match get_id_of_tag(conn, tag)? {
+            Some(tag_id) => tag_id,
+            None => return Ok(false),
+        };
+        conn.prepare_cached("UPDATE tags SET description = ?2 WHERE id = ?1")?
+            .execute(params![tag_id, description])?;
+        Ok(true)
+    })
+}
+
+/// Returns the description of a tag, see [`get_item_note`].
+pub fn get_tag_description(conn: &Connection, tag: &str) -> Result<Option<Option<String>>> {
+    match get_id_of_tag(conn, tag)? {
+        Some(tag_id) => conn.prepare_cached("SELECT description FROM tags WHERE id = ?1")?
+            .query_row(params![tag_id], |row| row.get(0))
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn search(conn: &Connection, expr: &Expr) -> Result<Vec<String>> {
+    search_tagged(conn, expr, &TimeRange::default(), false)
+}
+
+/// Like [`search`], but only returns the items tagged with any tag within
+/// `range`, and tags in `expr` only match associations made within `range`.
+/// Sorts by the time the items were last tagged, most recent first, instead
+/// of by path if `by_tagged_at` is set.
+pub fn search_tagged(conn: &Connection, expr: &Expr, range: &TimeRange, by_tagged_at: bool) -> Result<Vec<String>> {
+    let mut params: Vec<Value> = Vec::new();
+    let mut sql = format!(
+        "SELECT path, (SELECT max(tagged_at) FROM item_tags WHERE item_id = items.id) AS last_tagged \
+         FROM items WHERE {}",
+        expr.to_sql(range, &mut params)
+    );
+    if !range.is_unlimited() {
+        let conditions = range.conditions("tagged_at", &mut params);
+        sql.push_str(&format!(" AND items.id IN (SELECT item_id FROM item_tags WHERE {})", conditions.join(" AND ")));
+    }
+    if by_tagged_at {
+        sql.push_str(" ORDER BY last_tagged IS NULL, last_tagged DESC, path");
+    } else {
+        sql.push_str(" ORDER BY path");
+    }
+    let mut stmt = conn.prepare(sql.as_str())?;
+    let paths = stmt.query_map(&params, |row| row.get(0))?;
+    paths.collect()
+}
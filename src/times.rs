@@ -0,0 +1,85 @@
+//! Points in time given on the command line, e.g. `--since 7d` or
+//! `--before 2026-01-01`.
+//!
+//! A time is either an age such as `30m`, `12h`, `7d` or `2w` before now, or
+//! a local date (`YYYY-MM-DD`, or `today`) or date and time
+//! (`YYYY-MM-DD HH:MM[:SS]`). Times are converted to UTC in the format of
+//! SQLite's `CURRENT_TIMESTAMP`, so they compare with stored timestamps as
+//! text.
+use chrono::{DateTime, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use rusqlite::types::Value;
+
+const STORED_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn parse_age(spec: &str) -> Option<Duration> {
+    let unit = spec.chars().last()?;
+    let amount: i64 = spec[..spec.len() - unit.len_utf8()].parse().ok().filter(|amount| *amount >= 0)?;
+    match unit {
+        's' => Duration::try_seconds(amount),
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+fn from_local(time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match Local.from_local_datetime(&time) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => Some(time.with_timezone(&Utc)),
+        LocalResult::None => None,
+    }
+}
+
+/// Returns the stored form of the time `spec`, with ages counted back from
+/// `now`.
+pub fn parse_time(spec: &str, now: DateTime<Utc>) -> Result<String, String> {
+    let spec = spec.trim();
+    let time = if let Some(age) = parse_age(spec) {
+        now.checked_sub_signed(age)
+    } else if spec == "today" {
+        from_local(now.with_timezone(&Local).date_naive().and_hms_opt(0, 0, 0).unwrap())
+    } else if let Ok(date) = NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+        from_local(date.and_hms_opt(0, 0, 0).unwrap())
+    } else if let Some(time) = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(spec, format).ok())
+    {
+        from_local(time)
+    } else {
+        return Err(format!("Invalid time {}, expected e.g. 7d, 12h, 2026-01-01 or '2026-01-01 18:00'", spec));
+    };
+    time.map(|time| time.format(STORED_FORMAT).to_string())
+        .ok_or_else(|| format!("Invalid time {}", spec))
+}
+
+/// Limits results to what was tagged within a range. Associations made
+/// before tagging times were recorded are outside of every limited range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeRange {
+    /// Stored form of the earliest time, inclusive.
+    pub since: Option<String>,
+    /// Stored form of the latest time, exclusive.
+    pub before: Option<String>,
+}
+
+impl TimeRange {
+    pub fn is_unlimited(&self) -> bool {
+        self.since.is_none() && self.before.is_none()
+    }
+
+    /// Returns the conditions on `column` for the range, to be joined with
+    /// `AND`. Their parameters are appended to `params`.
+    pub fn conditions(&self, column: &str, params: &mut Vec<Value>) -> Vec<String> {
+        let mut conditions = Vec::new();
+        if let Some(since) = &self.since {
+            params.push(Value::Text(since.clone()));
+            conditions.push(format!("{} >= ?", column));
+        }
+        if let Some(before) = &self.before {
+            params.push(Value::Text(before.clone()));
+            conditions.push(format!("{} < ?", column));
+        }
+        conditions
+    }
+}
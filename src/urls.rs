@@ -0,0 +1,58 @@
+//! Normalization of URL items, so that equivalent URLs are stored as one item.
+//!
+//! Scheme and host are lowercased and default ports are removed by parsing.
+//! Tracking parameters such as `utm_source` or `fbclid` are dropped, and the
+//! fragment is kept or stripped according to a [`FragmentPolicy`].
+use url::{form_urlencoded, Url};
+
+pub use url::ParseError;
+
+/// Query parameters that only identify where a link was found.
+const TRACKING_PARAMETERS: &[&str] = &["fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FragmentPolicy {
+    /// Keep fragments, so `page#intro` and `page#usage` are different items.
+    Keep,
+    /// Strip fragments, so all sections of a page are one item.
+    Strip,
+}
+
+/// Returns `true` if an argument to `rtag tag` is meant as a URL rather than
+/// a path, i.e. it starts with a scheme followed by `://`. The URL may still
+/// be invalid.
+pub fn is_url(arg: &str) -> bool {
+    match arg.trim().split_once("://") {
+        Some((scheme, _)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+fn is_tracking_parameter(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMETERS.contains(&name)
+}
+
+/// Returns the normalized form of `url` that is stored in the database.
+pub fn normalize_url(url: &str, fragment_policy: FragmentPolicy) -> Result<String, ParseError> {
+    let mut url = Url::parse(url.trim())?;
+    if let Some(query) = url.query() {
+        // the kept parameters are copied as written, so that the query is
+        // not re-encoded differently than without tracking parameters
+        let query = query
+            .split('&')
+            .filter(|part| !form_urlencoded::parse(part.as_bytes()).any(|(name, _)| is_tracking_parameter(&name)))
+            .collect::<Vec<_>>()
+            .join("&");
+        url.set_query(Some(&query));
+    }
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+    if fragment_policy == FragmentPolicy::Strip || url.fragment() == Some("") {
+        url.set_fragment(None);
+    }
+    Ok(url.into())
+}
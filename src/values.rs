@@ -0,0 +1,86 @@
+//! Typed values of tag associations, e.g. `rating=4` or `due=2026-11-01`.
+//!
+//! The type of a value is inferred from its text: integers, floats, ISO dates
+//! (`YYYY-MM-DD`, or `today`) and strings for everything else. Values are
+//! stored together with their type, so numbers are ordered numerically, dates
+//! chronologically, and values of different types are never compared.
+use chrono::{Local, NaiveDate};
+use rusqlite::types::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagValue {
+    Int(i64),
+    Float(f64),
+    Date(NaiveDate),
+    Text(String),
+}
+
+const NUMBER_TYPES: &[&str] = &["int", "float"];
+const DATE_TYPES: &[&str] = &["date"];
+const STRING_TYPES: &[&str] = &["string"];
+
+impl TagValue {
+    pub fn parse(value: &str) -> TagValue {
+        if let Ok(int) = value.parse() {
+            TagValue::Int(int)
+        } else if let Some(float) = value.parse().ok().filter(|float: &f64| float.is_finite()) {
+            TagValue::Float(float)
+        } else if value == "today" {
+            TagValue::Date(Local::now().date_naive())
+        } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            TagValue::Date(date)
+        } else {
+            TagValue::Text(String::from(value))
+        }
+    }
+
+    /// The name stored in `item_tags.value_type`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TagValue::Int(_) => "int",
+            TagValue::Float(_) => "float",
+            TagValue::Date(_) => "date",
+            TagValue::Text(_) => "string",
+        }
+    }
+
+    /// The types of the values this value can be compared with.
+    pub fn comparable_types(&self) -> &'static [&'static str] {
+        match self {
+            TagValue::Int(_) | TagValue::Float(_) => NUMBER_TYPES,
+            TagValue::Date(_) => DATE_TYPES,
+            TagValue::Text(_) => STRING_TYPES,
+        }
+    }
+
+    /// Returns the value as stored in `item_tags.value`. Dates are stored as
+    /// ISO text, which orders chronologically.
+    pub fn to_sql_value(&self) -> Value {
+        match self {
+            TagValue::Int(int) => Value::Integer(*int),
+            TagValue::Float(float) => Value::Real(*float),
+            TagValue::Date(date) => Value::Text(date.format("%Y-%m-%d").to_string()),
+            TagValue::Text(text) => Value::Text(text.clone()),
+        }
+    }
+}
+
+impl fmt::Display for TagValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagValue::Int(int) => write!(f, "{}", int),
+            TagValue::Float(float) => write!(f, "{}", float),
+            TagValue::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            TagValue::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/// Splits `rating=4` into the tag and its value. A tag without `=` has no value.
+pub fn split_tag_value(tag: &str) -> (&str, Option<TagValue>) {
+    match tag.split_once('=') {
+        Some((name, value)) => (name, Some(TagValue::parse(value))),
+        None => (tag, None),
+    }
+}
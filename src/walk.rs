@@ -0,0 +1,67 @@
+//! Recursive directory walking for `rtag tag -r`.
+//!
+//! Files ignored by `.gitignore`, `.ignore` or `.rtagignore` files are
+//! skipped, as are hidden files unless requested. The `.git` and `.rtag`
+//! directories are never entered.
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+use crate::location::LOCAL_DIR_NAME;
+
+pub const IGNORE_FILE_NAME: &str = ".rtagignore";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryType {
+    File,
+    Dir,
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalkOptions {
+    pub hidden: bool,
+    pub max_depth: Option<usize>,
+    pub entry_type: EntryType,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            hidden: false,
+            max_depth: None,
+            entry_type: EntryType::File,
+        }
+    }
+}
+
+/// Returns all entries below `dir` of the requested type, excluding `dir`
+/// itself. Unreadable entries are reported on stderr and skipped.
+pub fn walk_dir(dir: &Path, options: &WalkOptions) -> Vec<PathBuf> {
+    let walker = WalkBuilder::new(dir)
+        .hidden(!options.hidden)
+        .max_depth(options.max_depth)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .filter_entry(|entry| entry.file_name() != ".git" && entry.file_name() != LOCAL_DIR_NAME)
+        .build();
+    let mut paths = Vec::new();
+    for entry in walker {
+        match entry {
+            Ok(entry) if entry.depth() > 0 => {
+                let is_dir = entry.file_type().is_some_and(|file_type| file_type.is_dir());
+                let wanted = match options.entry_type {
+                    EntryType::File => !is_dir,
+                    EntryType::Dir => is_dir,
+                    EntryType::Any => true,
+                };
+                if wanted {
+                    paths.push(entry.into_path());
+                }
+            }
+            Ok(_) => {}
+            Err(error) => eprintln!("Skipping entry: {}", error),
+        }
+    }
+    paths.sort();
+    paths
+}
@@ -0,0 +1,84 @@
+//! Applies file system changes reported by `rtag watch` to the store.
+//!
+//! Renames and moves within the watched directories are followed, tags of
+//! deleted files are kept and their items marked as missing. A file that
+//! reappears at its old path, e.g. when an editor saves by replacing it, is
+//! no longer missing.
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::Event;
+use rusqlite::{Connection, Result};
+use std::path::{Path, PathBuf};
+
+use crate::location::{DbLocation, LOCAL_DIR_NAME};
+use crate::rtag_sqlite::{mark_missing, mark_present, move_path};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Moved(PathBuf, PathBuf),
+    Removed(PathBuf),
+    Created(PathBuf),
+}
+
+/// Converts a file system event into the changes relevant for the store.
+/// A move is reported as `Removed`, `Created` and finally `Moved` when both
+/// of its ends are watched, so each event can be applied on its own.
+pub fn changes_of(event: &Event) -> Vec<Change> {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            vec![Change::Moved(event.paths[0].clone(), event.paths[1].clone())]
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_) => {
+            event.paths.iter().cloned().map(Change::Removed).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) | EventKind::Create(_) => {
+            event.paths.iter().cloned().map(Change::Created).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns `true` for the files of the database itself, such as its journal,
+/// which change with every write. Like [`crate::walk`], the whole `.rtag`
+/// directory of a project-local database is skipped.
+fn is_store_file(db: &DbLocation, path: &Path) -> bool {
+    if let Some(root) = &db.root {
+        if path.starts_with(root.join(LOCAL_DIR_NAME)) {
+            return true;
+        }
+    }
+    let db_name = db.path.file_name().unwrap_or_default().to_string_lossy();
+    path.parent() == db.path.parent()
+        && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(db_name.as_ref()))
+}
+
+/// Applies a change and returns the number of affected items. Changes of the
+/// database's own files are ignored.
+pub fn apply_change(conn: &Connection, db: &DbLocation, change: &Change) -> Result<usize> {
+    let paths = match change {
+        Change::Moved(from, to) => vec![from, to],
+        Change::Removed(path) | Change::Created(path) => vec![path],
+    };
+    if paths.iter().any(|path| is_store_file(db, path)) {
+        return Ok(0);
+    }
+    match change {
+        Change::Moved(from, to) => move_path(conn, &db.to_stored_path(from), &db.to_stored_path(to)),
+        Change::Removed(path) => mark_missing(conn, &db.to_stored_path(path)),
+        Change::Created(path) => mark_present(conn, &db.to_stored_path(path)),
+    }
+}
+
+/// Returns the directories to watch for the given tagged paths: their parent
+/// directories, leaving out directories below another one in the list.
+pub fn default_roots(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut parents: Vec<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+    parents.sort();
+    parents.dedup();
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for parent in parents {
+        if !roots.iter().any(|root| parent.starts_with(root)) {
+            roots.push(parent.to_path_buf());
+        }
+    }
+    roots
+}
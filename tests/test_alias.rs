@@ -0,0 +1,57 @@
+mod alias_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{add_alias, create_new_tag, get_aliases, initialize_tables, insert_path, remove_alias, search};
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_alias_resolves_to_canonical_tag() {
+        let conn = create_new_db();
+        add_alias(&conn, "ml", "ai/machine-learning").unwrap();
+        add_alias(&conn, "ML", "ai/machine-learning").unwrap();
+        insert_path(&conn, "/a", "ml").unwrap();
+        insert_path(&conn, "/a", "ML").unwrap();
+        insert_path(&conn, "/b", "ai/machine-learning").unwrap();
+
+        let tags: i64 = conn.query_row("SELECT count(*) FROM tags", NO_PARAMS, |row| row.get(0)).unwrap();
+        assert_eq!(tags, 2);
+        let rows: i64 = conn.query_row("SELECT count(*) FROM item_tags", NO_PARAMS, |row| row.get(0)).unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(search(&conn, &parse("ML").unwrap()).unwrap(), vec!["/a", "/b"]);
+        assert_eq!(search(&conn, &parse("ai AND NOT ml").unwrap()).unwrap(), Vec::<String>::new());
+        assert_eq!(
+            get_aliases(&conn).unwrap(),
+            vec![
+                (String::from("ML"), String::from("ai/machine-learning")),
+                (String::from("ml"), String::from("ai/machine-learning"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alias_with_value_separator() {
+        let conn = create_new_db();
+        // `x=y` would be read as the tag `x` with the value `y`
+        assert!(add_alias(&conn, "x=y", "machine-learning").is_err());
+        assert!(get_aliases(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_alias_and_tag_names_are_disjoint() {
+        let conn = create_new_db();
+        create_new_tag(&conn, "ml").unwrap();
+        assert!(add_alias(&conn, "ml", "machine-learning").is_err());
+        add_alias(&conn, "machine_learning", "machine-learning").unwrap();
+        assert!(create_new_tag(&conn, "machine_learning").is_err());
+
+        assert!(remove_alias(&conn, "machine_learning").unwrap());
+        assert!(!remove_alias(&conn, "machine_learning").unwrap());
+        create_new_tag(&conn, "machine_learning").unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+mod check_tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use rtag::check::{check_path, Finding, Problem};
+    use rtag::fingerprint::Fingerprint;
+
+    #[test]
+    fn test_check_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a");
+        fs::write(&file, "abc").unwrap();
+        let fingerprint = Fingerprint::of(&file, false).unwrap();
+        assert_eq!(check_path(&file, Some(&fingerprint)), None);
+        // items tagged before fingerprints were recorded are only checked for existence
+        fs::write(&file, "abcd").unwrap();
+        assert_eq!(check_path(&file, None), None);
+        assert_eq!(check_path(&file, Some(&fingerprint)), Some(Problem::Modified));
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(check_path(&file, Some(&fingerprint)), Some(Problem::Missing));
+        symlink(dir.path(), &file).unwrap();
+        assert_eq!(check_path(&file, Some(&fingerprint)), Some(Problem::Symlink));
+        assert_eq!(check_path(dir.path(), Some(&fingerprint)), None);
+    }
+
+    #[test]
+    fn test_finding_as_json() {
+        let finding = Finding { path: String::from("/a"), problem: Problem::Missing };
+        assert_eq!(serde_json::to_string(&finding).unwrap(), r#"{"path":"/a","problem":"missing"}"#);
+        assert!(Problem::Symlink.is_dangling());
+        assert!(!Problem::Modified.is_dangling());
+    }
+}
@@ -0,0 +1,79 @@
+mod fingerprint_tests {
+    use rusqlite::Connection;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use rtag::fingerprint::{content_hash, find_moved, Fingerprint, Match};
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{get_fingerprints, initialize_tables, insert_path, move_item, search, set_fingerprint};
+
+    fn fingerprint(inode: i64, size: i64, mtime: i64, content_hash: Option<&str>) -> Fingerprint {
+        Fingerprint { dev: 1, inode, size, mtime, content_hash: content_hash.map(String::from) }
+    }
+
+    fn candidate(path: &str, fingerprint: Fingerprint) -> (PathBuf, Fingerprint) {
+        (PathBuf::from(path), fingerprint)
+    }
+
+    #[test]
+    fn test_find_moved() {
+        let item = fingerprint(10, 5, 100, None);
+        let candidates = vec![
+            candidate("/copy", fingerprint(20, 5, 100, None)),
+            candidate("/moved", fingerprint(10, 5, 100, None)),
+        ];
+        let no_hash = |_: &Path| -> Option<String> { panic!("must not hash without a recorded hash") };
+        assert_eq!(find_moved(&item, &candidates, no_hash), Some(Match::Certain(PathBuf::from("/moved"))));
+        // without the inode, a copy with the same size and mtime needs confirmation
+        assert_eq!(
+            find_moved(&item, &candidates[..1], no_hash),
+            Some(Match::Ambiguous(vec![PathBuf::from("/copy")]))
+        );
+        assert_eq!(find_moved(&fingerprint(30, 5, 200, None), &candidates, no_hash), None);
+
+        // a recorded hash picks the only copy with the same contents
+        let item = fingerprint(10, 5, 100, Some("abc"));
+        let candidates = vec![
+            candidate("/other", fingerprint(20, 5, 300, None)),
+            candidate("/copy", fingerprint(21, 5, 400, None)),
+            candidate("/large", fingerprint(22, 6, 100, None)),
+        ];
+        let hash_of = |path: &Path| {
+            assert_ne!(path, Path::new("/large"));
+            Some(String::from(if path == Path::new("/copy") { "abc" } else { "def" }))
+        };
+        assert_eq!(find_moved(&item, &candidates, hash_of), Some(Match::Certain(PathBuf::from("/copy"))));
+    }
+
+    #[test]
+    fn test_fingerprint_of_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "abc").unwrap();
+        let fingerprint = Fingerprint::of(&path, true).unwrap();
+        assert_eq!(fingerprint.size, 3);
+        assert_eq!(
+            fingerprint.content_hash.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(content_hash(&path).unwrap(), fingerprint.content_hash.unwrap());
+        assert_eq!(Fingerprint::of(dir.path(), true).unwrap().content_hash, None);
+    }
+
+    #[test]
+    fn test_move_item_keeps_tags() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "rust").unwrap();
+        insert_path(&conn, "/b", "rust").unwrap();
+        set_fingerprint(&conn, "/a", &fingerprint(10, 5, 100, Some("abc"))).unwrap();
+        assert_eq!(get_fingerprints(&conn).unwrap(), vec![(String::from("/a"), fingerprint(10, 5, 100, Some("abc")))]);
+
+        assert!(move_item(&conn, "/a", "/c", &fingerprint(11, 5, 100, None)).unwrap());
+        assert!(!move_item(&conn, "/a", "/d", &fingerprint(11, 5, 100, None)).unwrap());
+        assert_eq!(search(&conn, &parse("rust").unwrap()).unwrap(), vec!["/b", "/c"]);
+        assert_eq!(get_fingerprints(&conn).unwrap(), vec![(String::from("/c"), fingerprint(11, 5, 100, None))]);
+        // moving onto a tagged path is refused
+        assert!(move_item(&conn, "/c", "/b", &fingerprint(11, 5, 100, None)).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+mod hierarchy_tests {
+    use rusqlite::Connection;
+
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{
+        delete_by_id, get_descendant_ids, get_ids_of_tags, get_tags, initialize_tables, insert_path, parent_tag_name,
+        search, tag_tree_lines,
+    };
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        conn
+    }
+
+    fn id_of(conn: &Connection, tag: &str) -> i32 {
+        get_ids_of_tags(conn, &[String::from(tag)]).unwrap()[0]
+    }
+
+    #[test]
+    fn test_parent_tag_name() {
+        assert_eq!(parent_tag_name("lang/rust/async"), Some("lang/rust"));
+        assert_eq!(parent_tag_name("lang"), None);
+        assert_eq!(parent_tag_name("/lang"), None);
+    }
+
+    #[test]
+    fn test_ancestors_are_created_and_matched() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "lang/rust/async").unwrap();
+        insert_path(&conn, "/b", "lang/python").unwrap();
+        insert_path(&conn, "/c", "lang/rust").unwrap();
+
+        let names: Vec<String> = get_tags(&conn).unwrap().into_iter().map(|t| t.tag_name).collect();
+        assert_eq!(names, vec!["lang", "lang/python", "lang/rust", "lang/rust/async"]);
+
+        assert_eq!(search(&conn, &parse("lang/rust").unwrap()).unwrap(), vec!["/a", "/c"]);
+        assert_eq!(search(&conn, &parse("lang").unwrap()).unwrap(), vec!["/a", "/b", "/c"]);
+        assert_eq!(search(&conn, &parse("lang/rust/async").unwrap()).unwrap(), vec!["/a"]);
+
+        assert_eq!(
+            tag_tree_lines(&get_tags(&conn).unwrap()),
+            vec!["lang", "├── python", "└── rust", "    └── async"]
+        );
+    }
+
+    #[test]
+    fn test_delete_parent() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "lang/rust/async").unwrap();
+        let lang = id_of(&conn, "lang");
+        assert_eq!(get_descendant_ids(&conn, lang).unwrap().len(), 2);
+
+        // without cascading, the children stay below an empty tag of the same name
+        let rust = id_of(&conn, "lang/rust");
+        delete_by_id(&conn, &[rust]).unwrap();
+        assert_eq!(tag_tree_lines(&get_tags(&conn).unwrap()), vec!["lang", "└── rust", "    └── async"]);
+        assert_ne!(id_of(&conn, "lang/rust"), rust);
+        assert_eq!(search(&conn, &parse("lang/rust").unwrap()).unwrap(), vec!["/a"]);
+        insert_path(&conn, "/b", "lang/rust").unwrap();
+        delete_by_id(&conn, &[id_of(&conn, "lang/rust")]).unwrap();
+        assert_eq!(search(&conn, &parse("lang/rust").unwrap()).unwrap(), vec!["/a"]);
+
+        let mut ids = vec![lang];
+        ids.extend(get_descendant_ids(&conn, lang).unwrap());
+        delete_by_id(&conn, &ids).unwrap();
+        assert!(get_tags(&conn).unwrap().is_empty());
+    }
+}
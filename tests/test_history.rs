@@ -0,0 +1,132 @@
+mod history_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::fingerprint::Fingerprint;
+    use rtag::history::{get_history, undo};
+    use rtag::rtag_sqlite::{
+        add_alias, create_new_tag, delete_by_tag, get_item_note, initialize_tables, insert_path, insert_paths,
+        mark_missing, mark_present, move_item, search, set_item_note,
+    };
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        conn
+    }
+
+    /// Returns the tags, aliases and associations, to compare states.
+    fn snapshot(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT 'tag ' || id || ' ' || tag_name || ' ' || COALESCE(parent_id, '-') FROM tags
+                 UNION ALL SELECT 'alias ' || alias || ' ' || tag_id FROM tag_alias
+                 UNION ALL SELECT 'item ' || id || ' ' || path FROM items
+                 UNION ALL SELECT 'item_tag ' || item_id || ' ' || tag_id || ' ' || COALESCE(value, '-') FROM item_tags
+                 ORDER BY 1",
+            )
+            .unwrap();
+        let rows = stmt.query_map(NO_PARAMS, |row| row.get(0)).unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_operations_are_logged() {
+        let conn = create_new_db();
+        let tags = vec![String::from("lang/rust"), String::from("rating=4")];
+        insert_paths(&conn, &[String::from("/a"), String::from("/b")], &tags).unwrap();
+        create_new_tag(&conn, "empty").unwrap();
+        delete_by_tag(&conn, &[String::from("empty")]).unwrap();
+
+        let history = get_history(&conn, None).unwrap();
+        let operations: Vec<&str> = history.iter().map(|operation| operation.operation.as_str()).collect();
+        assert_eq!(operations, vec!["delete", "create", "tag"]);
+        assert_eq!(history[2].arguments, r#"{"type":"path","paths":["/a","/b"],"tags":["lang/rust","rating=4"]}"#);
+        // 3 tags, 2 items and 4 associations, plus the last used times of the
+        // 2 used tags, which are updated again only if a second passed
+        assert!((3 + 2 + 4 + 2..=3 + 2 + 4 + 4).contains(&history[2].affected_rows));
+        assert_eq!(get_history(&conn, Some(1)).unwrap().len(), 1);
+
+        assert!(conn.execute("DELETE FROM history", NO_PARAMS).is_err());
+        assert!(conn.execute("UPDATE history_changes SET row_id = 0", NO_PARAMS).is_err());
+    }
+
+    #[test]
+    fn test_undo_delete_restores_everything() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "lang/rust").unwrap();
+        insert_path(&conn, "/a", "rating=4").unwrap();
+        insert_path(&conn, "/b", "lang/rust/async").unwrap();
+        add_alias(&conn, "rs", "lang/rust").unwrap();
+        let before = snapshot(&conn);
+
+        delete_by_tag(&conn, &[String::from("lang/rust"), String::from("rating")]).unwrap();
+        assert_ne!(snapshot(&conn), before);
+        let undone = undo(&conn, 1).unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(undone[0].operation, "delete");
+        assert_eq!(snapshot(&conn), before);
+        assert_eq!(search(&conn, &rtag::query::parse("rs AND rating>3").unwrap()).unwrap(), vec!["/a"]);
+
+        let history = get_history(&conn, None).unwrap();
+        assert_eq!(history[0].operation, "undo");
+        assert_eq!(history[1].undone_by, Some(history[0].id));
+    }
+
+    #[test]
+    fn test_undo_skips_undone_operations() {
+        let conn = create_new_db();
+        let empty = snapshot(&conn);
+        insert_path(&conn, "/a", "first").unwrap();
+        let first = snapshot(&conn);
+        insert_path(&conn, "/a", "second").unwrap();
+
+        undo(&conn, 1).unwrap();
+        assert_eq!(snapshot(&conn), first);
+        let undone = undo(&conn, 5).unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(snapshot(&conn), empty);
+        assert!(undo(&conn, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_repair() {
+        let conn = create_new_db();
+        insert_path(&conn, "/x", "photos").unwrap();
+        set_item_note(&conn, "/x", Some("holiday")).unwrap();
+        let fingerprint = Fingerprint { dev: 1, inode: 2, size: 3, mtime: 4, content_hash: None };
+        assert!(move_item(&conn, "/x", "/y", &fingerprint).unwrap());
+        assert_eq!(get_history(&conn, Some(1)).unwrap()[0].operation, "move");
+
+        undo(&conn, 1).unwrap();
+        assert_eq!(get_item_note(&conn, "/x").unwrap(), Some(Some(String::from("holiday"))));
+        assert_eq!(get_item_note(&conn, "/y").unwrap(), None);
+        undo(&conn, 1).unwrap();
+        assert_eq!(get_item_note(&conn, "/x").unwrap(), Some(None));
+    }
+
+    #[test]
+    fn test_undo_restores_only_changed_columns() {
+        let conn = create_new_db();
+        insert_path(&conn, "/x", "photos").unwrap();
+        set_item_note(&conn, "/x", Some("holiday")).unwrap();
+        conn.execute("UPDATE items SET path = '/y' WHERE path = '/x'", NO_PARAMS).unwrap();
+
+        undo(&conn, 1).unwrap();
+        assert_eq!(get_item_note(&conn, "/y").unwrap(), Some(None));
+        assert_eq!(get_item_note(&conn, "/x").unwrap(), None);
+    }
+
+    #[test]
+    fn test_operations_without_changes_are_not_logged() {
+        let conn = create_new_db();
+        insert_path(&conn, "/x", "photos").unwrap();
+        assert_eq!(mark_present(&conn, "/x").unwrap(), 0);
+        assert_eq!(get_history(&conn, None).unwrap().len(), 1);
+
+        assert_eq!(mark_missing(&conn, "/x").unwrap(), 1);
+        assert_eq!(get_history(&conn, Some(1)).unwrap()[0].operation, "missing");
+        undo(&conn, 1).unwrap();
+        assert_eq!(search(&conn, &rtag::query::parse("photos").unwrap()).unwrap(), vec!["/x"]);
+        assert_eq!(mark_present(&conn, "/x").unwrap(), 0);
+    }
+}
@@ -0,0 +1,73 @@
+mod kinds_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use rtag::kinds::{Context, ItemKind, Registry};
+    use rtag::location::DbLocation;
+    use rtag::urls::FragmentPolicy;
+
+    /// A kind as a user of the library would add it.
+    struct IssueKind;
+
+    impl ItemKind for IssueKind {
+        fn name(&self) -> &'static str {
+            "issue"
+        }
+
+        fn accepts(&self, arg: &str) -> bool {
+            arg.starts_with('#') && arg[1..].parse::<u32>().is_ok()
+        }
+
+        fn resolve(&self, arg: &str, _context: &Context) -> Result<Vec<String>, String> {
+            Ok(vec![String::from(arg)])
+        }
+
+        fn exists(&self, _stored: &str, _db: &DbLocation) -> Option<bool> {
+            None
+        }
+    }
+
+    fn context(db: &DbLocation) -> Context<'_> {
+        Context { db, walk_options: None, fragment_policy: FragmentPolicy::Keep }
+    }
+
+    #[test]
+    fn test_builtin_kinds() {
+        let kinds = Registry::default();
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("a.rs"), "").unwrap();
+        fs::write(root.join(".hidden.rs"), "").unwrap();
+        let db = DbLocation { path: root.join(".rtag").join("rtag.db"), root: Some(root.clone()) };
+
+        let kind_name = |arg: &str| kinds.kind_of(arg).unwrap().name();
+        assert_eq!(kind_name("https://example.com"), "url");
+        assert_eq!(kind_name("note:buy milk"), "note");
+        assert_eq!(kind_name("src/main.rs"), "path");
+
+        let path = kinds.get("path").unwrap();
+        let pattern = format!("{}/*.rs", root.display());
+        assert_eq!(path.resolve(&pattern, &context(&db)).unwrap(), vec!["a.rs"]);
+        assert!(path.resolve("/does/not/exist", &context(&db)).is_err());
+        assert_eq!(path.exists("a.rs", &db), Some(true));
+        assert_eq!(path.exists("b.rs", &db), Some(false));
+
+        let note = kinds.get("note").unwrap();
+        assert_eq!(note.resolve("note: buy milk ", &context(&db)).unwrap(), vec!["note:buy milk"]);
+        assert!(note.resolve("note:", &context(&db)).is_err());
+        assert_eq!(kinds.display("note", "note:buy milk"), "\"buy milk\"");
+        assert_eq!(kinds.display("unknown", "x"), "x");
+        assert_eq!(kinds.get("url").unwrap().exists("https://example.com/", &db), None);
+    }
+
+    #[test]
+    fn test_register_kind() {
+        let mut kinds = Registry::default();
+        let db = DbLocation { path: PathBuf::from("/rtag.db"), root: None };
+        assert_eq!(kinds.kind_of("#42").unwrap().name(), "path");
+        kinds.register(Box::new(IssueKind));
+        assert_eq!(kinds.kind_of("#42").unwrap().name(), "issue");
+        assert_eq!(kinds.kind_of("#readme").unwrap().name(), "path");
+        assert_eq!(kinds.get("issue").unwrap().resolve("#42", &context(&db)).unwrap(), vec!["#42"]);
+    }
+}
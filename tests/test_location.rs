@@ -0,0 +1,46 @@
+mod location_tests {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use rtag::location::{data_dir, find_local_db, init_local_db, resolve_db_location, DbLocation};
+
+    #[test]
+    fn test_data_dir() {
+        let xdg = Some(OsString::from("/data"));
+        let home = Some(OsString::from("/home/me"));
+        assert_eq!(data_dir(xdg, home.clone()), Some(PathBuf::from("/data")));
+        assert_eq!(data_dir(None, home.clone()), Some(PathBuf::from("/home/me/.local/share")));
+        // relative values of XDG_DATA_HOME are invalid and must be ignored
+        assert_eq!(data_dir(Some(OsString::from("data")), home), Some(PathBuf::from("/home/me/.local/share")));
+        assert_eq!(data_dir(None, None), None);
+    }
+
+    #[test]
+    fn test_cli_option_wins() {
+        let db = Path::new("/tmp/some/rtag.db");
+        let location = resolve_db_location(Some(db), Path::new("/")).unwrap();
+        assert_eq!(location, DbLocation { path: db.to_path_buf(), root: None });
+    }
+
+    #[test]
+    fn test_local_db_discovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = fs::canonicalize(dir.path()).unwrap();
+        let nested = project.join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_local_db(&nested), None);
+
+        let db = init_local_db(&project).unwrap();
+        fs::write(&db.path, b"").unwrap();
+        assert_eq!(db.root, Some(project.clone()));
+        assert_eq!(find_local_db(&nested), Some(db.clone()));
+        assert_eq!(resolve_db_location(None, &nested).unwrap().root, Some(project.clone()));
+
+        assert_eq!(db.to_stored_path(&nested.join("a.rs")), "src/deep/a.rs");
+        assert_eq!(db.to_stored_path(&project), ".");
+        assert_eq!(db.to_stored_path(Path::new("/elsewhere/b")), "/elsewhere/b");
+        assert_eq!(db.to_absolute_path("src/deep/a.rs"), nested.join("a.rs"));
+        assert_eq!(db.to_absolute_path("/elsewhere/b"), PathBuf::from("/elsewhere/b"));
+    }
+}
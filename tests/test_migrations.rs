@@ -0,0 +1,93 @@
+mod migration_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::migrations::{current_version, migrate, pending_migrations, MIGRATIONS};
+
+    #[test]
+    fn test_fresh_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert_eq!(pending_migrations(&conn).unwrap().len(), MIGRATIONS.len());
+        // a dry run must not create anything
+        assert_eq!(current_version(&conn).unwrap(), 0);
+
+        assert_eq!(migrate(&conn).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), MIGRATIONS.last().unwrap().version);
+        assert!(migrate(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unversioned_database_keeps_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dim_tag (id INTEGER PRIMARY KEY, tag_name VARCHAR UNIQUE, time_created TIMESTAMP DEFAULT CURRENT_TIMESTAMP);
+             CREATE TABLE fct_tag (id INTEGER, path VARCHAR);
+             INSERT INTO dim_tag (tag_name) VALUES ('rust');
+             INSERT INTO fct_tag (id, path) VALUES (1, '/a');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        let count: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM tags JOIN item_tags ON item_tags.tag_id = tags.id
+                 JOIN items ON items.id = item_tags.item_id WHERE tag_name = 'rust' AND path = '/a'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_existing_hierarchical_tags_get_parents() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dim_tag (id INTEGER PRIMARY KEY, tag_name VARCHAR UNIQUE, time_created TIMESTAMP DEFAULT CURRENT_TIMESTAMP);
+             CREATE TABLE fct_tag (id INTEGER, path VARCHAR);
+             INSERT INTO dim_tag (tag_name) VALUES ('lang/rust/async');
+             INSERT INTO dim_tag (tag_name) VALUES ('lang');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        let parent: String = conn
+            .query_row(
+                "SELECT p.tag_name FROM tags c JOIN tags p ON c.parent_id = p.id WHERE c.tag_name = 'lang/rust/async'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(parent, "lang/rust");
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM tags WHERE parent_id IS NULL", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_normalized_schema_drops_duplicates_and_dangling_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON").unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version < 6) {
+            (migration.up)(&conn).unwrap();
+        }
+        conn.execute_batch(
+            "INSERT INTO dim_tag (id, tag_name) VALUES (1, 'rust'), (2, 'paper');
+             INSERT INTO fct_tag (id, path) VALUES (1, '/a'), (1, '/a'), (2, '/a'), (1, '/b'), (7, '/c');
+             INSERT INTO tag_alias (alias, tag_id) VALUES ('rs', 1);",
+        )
+        .unwrap();
+        (MIGRATIONS.iter().find(|m| m.version == 6).unwrap().up)(&conn).unwrap();
+
+        let count = |sql: &str| -> i64 { conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT count(*) FROM items"), 2);
+        assert_eq!(count("SELECT count(*) FROM item_tags"), 3);
+        assert_eq!(count("SELECT count(*) FROM tag_alias"), 1);
+
+        conn.execute("DELETE FROM tags WHERE tag_name = 'rust'", NO_PARAMS).unwrap();
+        assert_eq!(count("SELECT count(*) FROM item_tags"), 1);
+        assert_eq!(count("SELECT count(*) FROM tag_alias"), 0);
+        assert!(conn.execute("INSERT INTO item_tags (item_id, tag_id) VALUES (1, 99)", NO_PARAMS).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+mod notes_tests {
+    use rusqlite::Connection;
+
+    use rtag::query::{parse, Expr};
+    use rtag::rtag_sqlite::{
+        add_alias, get_item_note, get_tag_description, initialize_tables, insert_item, insert_path, search,
+        set_item_note, set_tag_description, NOTE_ITEM,
+    };
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/paper1", "papers").unwrap();
+        insert_path(&conn, "/paper2", "papers").unwrap();
+        insert_item(&conn, "note:read the 50% paper", NOTE_ITEM, "todo").unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_item_notes() {
+        let conn = create_new_db();
+        assert_eq!(get_item_note(&conn, "/paper1").unwrap(), Some(None));
+        assert!(set_item_note(&conn, "/paper1", Some("Introduces attention")).unwrap());
+        assert_eq!(get_item_note(&conn, "/paper1").unwrap(), Some(Some(String::from("Introduces attention"))));
+        assert!(!set_item_note(&conn, "/untagged", Some("x")).unwrap());
+        assert_eq!(get_item_note(&conn, "/untagged").unwrap(), None);
+        assert!(set_item_note(&conn, "/paper1", None).unwrap());
+        assert_eq!(get_item_note(&conn, "/paper1").unwrap(), Some(None));
+    }
+
+    #[test]
+    fn test_tag_descriptions() {
+        let conn = create_new_db();
+        add_alias(&conn, "p", "papers").unwrap();
+        assert!(set_tag_description(&conn, "p", Some("Research papers")).unwrap());
+        assert_eq!(get_tag_description(&conn, "papers").unwrap(), Some(Some(String::from("Research papers"))));
+        assert!(!set_tag_description(&conn, "missing", Some("x")).unwrap());
+        assert_eq!(get_tag_description(&conn, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_search_notes() {
+        let conn = create_new_db();
+        set_item_note(&conn, "/paper1", Some("Introduces attention")).unwrap();
+        set_item_note(&conn, "/paper2", Some("Builds on 50_percent")).unwrap();
+        assert_eq!(parse("note:\"two words\"").unwrap(), Expr::Note(String::from("two words")));
+        assert!(parse("note:").is_err());
+
+        let search_str = |query: &str| search(&conn, &parse(query).unwrap()).unwrap();
+        assert_eq!(search_str("note:ATTENTION"), vec!["/paper1"]);
+        assert_eq!(search_str("papers AND NOT note:attention"), vec!["/paper2"]);
+        // LIKE wildcards in the text match literally
+        assert_eq!(search_str("note:50%"), vec!["note:read the 50% paper"]);
+        assert_eq!(search_str("note:\"the 50\""), vec!["note:read the 50% paper"]);
+    }
+}
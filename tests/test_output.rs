@@ -0,0 +1,52 @@
+mod output_tests {
+    use serde_json::{json, Value};
+
+    use rtag::output::{write_nul_delimited, write_records, Format, Template};
+
+    fn render(format: Format, records: &[Vec<Value>]) -> String {
+        let mut out = Vec::new();
+        write_records(&mut out, &format, &["ID", "PATH"], records).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_delimited_formats() {
+        let records = vec![vec![json!(1), json!("a,\"b\"")], vec![json!(2), json!("tab\there\nline\\")]];
+        assert_eq!(render(Format::Csv, &records), "ID,PATH\n1,\"a,\"\"b\"\"\"\n2,\"tab\there\nline\\\"\n");
+        assert_eq!(render(Format::Tsv, &records), "ID\tPATH\n1\ta,\"b\"\n2\ttab\\there\\nline\\\\\n");
+    }
+
+    #[test]
+    fn test_json_formats() {
+        let records = vec![vec![json!(1), json!("/a")], vec![json!(2), Value::Null]];
+        assert_eq!(render(Format::Ndjson, &records), "{\"id\":1,\"path\":\"/a\"}\n{\"id\":2,\"path\":null}\n");
+        let parsed: Value = serde_json::from_str(&render(Format::Json, &records)).unwrap();
+        assert_eq!(parsed, json!([{"id": 1, "path": "/a"}, {"id": 2, "path": null}]));
+        assert_eq!(render(Format::Json, &[]).trim(), "[]");
+    }
+
+    #[test]
+    fn test_nul_delimited() {
+        let mut out = Vec::new();
+        write_nul_delimited(&mut out, &[String::from("a b"), String::from("c\nd")]).unwrap();
+        assert_eq!(out, b"a b\0c\nd\0");
+    }
+
+    #[test]
+    fn test_templates() {
+        let format: Format = r#"{path}\t{tags|join(" ")} {{{id}}}"#.parse().unwrap();
+        let records = vec![vec![json!(7), json!("/a"), json!(["rust", "rating=4"])], vec![json!(8), json!("/b"), json!([])]];
+        let mut out = Vec::new();
+        write_records(&mut out, &format, &["ID", "PATH", "TAGS"], &records).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "/a\trust rating=4 {7}\n/b\t {8}\n");
+
+        let template: Template = "{tags}:{missing}".parse().unwrap();
+        assert_eq!(template.fields(), vec!["tags", "missing"]);
+        assert_eq!(template.render(&["TAGS"], &[json!(["a", "b"])]), "a,b:");
+        assert!("{path".parse::<Template>().is_err());
+        assert!("path}".parse::<Template>().is_err());
+        assert!("{tags|sort}".parse::<Template>().is_err());
+        assert_eq!("csv".parse::<Format>(), Ok(Format::Csv));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,52 @@
+mod query_tests {
+    use rusqlite::Connection;
+
+    use rtag::query::{parse, Expr};
+    use rtag::rtag_sqlite::{initialize_tables, insert_path, search};
+
+    fn tag(name: &str) -> Box<Expr> {
+        Box::new(Expr::Tag(String::from(name)))
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expr = parse("rust AND (paper OR draft) AND NOT archived").unwrap();
+        let expected = Expr::And(
+            Box::new(Expr::And(tag("rust"), Box::new(Expr::Or(tag("paper"), tag("draft"))))),
+            Box::new(Expr::Not(tag("archived"))),
+        );
+        assert_eq!(expr, expected);
+
+        let expr = parse("a or b and c").unwrap();
+        assert_eq!(expr, Expr::Or(tag("a"), Box::new(Expr::And(tag("b"), tag("c")))));
+    }
+
+    #[test]
+    fn test_parse_quoted_and_errors() {
+        assert_eq!(parse("\"O'Reilly notes\"").unwrap(), *tag("O'Reilly notes"));
+        assert_eq!(parse("\"and\"").unwrap(), *tag("and"));
+        assert_eq!(parse("rust AND").unwrap_err().position, 8);
+        assert!(parse("(rust OR paper").is_err());
+        assert!(parse("rust paper").is_err());
+        assert!(parse("\"rust").is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "rust").unwrap();
+        insert_path(&conn, "/a", "paper").unwrap();
+        insert_path(&conn, "/b", "rust").unwrap();
+        insert_path(&conn, "/b", "draft").unwrap();
+        insert_path(&conn, "/b", "archived").unwrap();
+        insert_path(&conn, "/c", "paper").unwrap();
+
+        let expr = parse("rust AND (paper OR draft) AND NOT archived").unwrap();
+        assert_eq!(search(&conn, &expr).unwrap(), vec![String::from("/a")]);
+        let expr = parse("NOT rust").unwrap();
+        assert_eq!(search(&conn, &expr).unwrap(), vec![String::from("/c")]);
+        let expr = parse("paper OR draft").unwrap();
+        assert_eq!(search(&conn, &expr).unwrap(), vec!["/a", "/b", "/c"]);
+    }
+}
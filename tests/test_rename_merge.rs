@@ -0,0 +1,74 @@
+mod rename_merge_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{
+        create_new_tag, get_aliases, get_tags, initialize_tables, insert_path, merge_tags, rename_tag, search,
+    };
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        conn
+    }
+
+    fn tag_names(conn: &Connection) -> Vec<String> {
+        get_tags(conn).unwrap().into_iter().map(|t| t.tag_name).collect()
+    }
+
+    #[test]
+    fn test_rename() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "lang/rust/async").unwrap();
+        assert!(rename_tag(&conn, "lang", "language", true).unwrap());
+        assert_eq!(tag_names(&conn), vec!["language", "language/rust", "language/rust/async"]);
+        assert_eq!(search(&conn, &parse("language/rust").unwrap()).unwrap(), vec!["/a"]);
+
+        // without cascading the children keep their names under a new parent
+        assert!(rename_tag(&conn, "language/rust", "rust", false).unwrap());
+        assert_eq!(tag_names(&conn), vec!["language", "language/rust", "language/rust/async", "rust"]);
+        assert_eq!(search(&conn, &parse("rust").unwrap()).unwrap(), Vec::<String>::new());
+        assert_eq!(search(&conn, &parse("language").unwrap()).unwrap(), vec!["/a"]);
+
+        assert!(!rename_tag(&conn, "missing", "other", true).unwrap());
+        assert!(rename_tag(&conn, "rust", "language", true).is_err());
+        assert!(rename_tag(&conn, "language", "language/sub", true).is_err());
+        for invalid in &["rating=4", "rating>4", "rating<4", "rating!"] {
+            assert!(rename_tag(&conn, "rust", invalid, true).is_err());
+            assert!(create_new_tag(&conn, invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "ml").unwrap();
+        insert_path(&conn, "/b", "ml").unwrap();
+        insert_path(&conn, "/b", "ML").unwrap();
+        insert_path(&conn, "/c", "machine_learning/deep").unwrap();
+        insert_path(&conn, "/b", "machine-learning").unwrap();
+        insert_path(&conn, "/d", "machine-learning/deep").unwrap();
+
+        let sources = vec![String::from("ml"), String::from("ML"), String::from("machine_learning"), String::from("nope")];
+        let missing = merge_tags(&conn, &sources, "machine-learning").unwrap();
+        assert_eq!(missing, vec!["nope"]);
+
+        assert_eq!(tag_names(&conn), vec!["machine-learning", "machine-learning/deep"]);
+        let rows: i64 = conn.query_row("SELECT count(*) FROM item_tags", NO_PARAMS, |row| row.get(0)).unwrap();
+        assert_eq!(rows, 4);
+        assert_eq!(search(&conn, &parse("ml").unwrap()).unwrap(), vec!["/a", "/b", "/c", "/d"]);
+        assert_eq!(search(&conn, &parse("machine-learning/deep").unwrap()).unwrap(), vec!["/c", "/d"]);
+        assert_eq!(get_aliases(&conn).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_merge_is_atomic() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "lang/rust").unwrap();
+        insert_path(&conn, "/b", "other").unwrap();
+        let sources = vec![String::from("other"), String::from("lang")];
+        assert!(merge_tags(&conn, &sources, "lang/rust").is_err());
+        assert_eq!(tag_names(&conn), vec!["lang", "lang/rust", "other"]);
+        assert!(get_aliases(&conn).unwrap().is_empty());
+    }
+}
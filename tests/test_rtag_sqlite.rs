@@ -0,0 +1,121 @@
+mod rtag_sqlite_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::kinds::Registry;
+    use rtag::output::Format;
+    use rtag::rtag_sqlite::{
+        delete_by_tag, get_tags_of_path, initialize_tables, insert_path, insert_paths, show_paths, show_tags,
+        ShowOptions, ShowSort,
+    };
+    use rtag::times::TimeRange;
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_quotes_are_stored_verbatim() {
+        let conn = create_new_db();
+        insert_path(&conn, "/notes/O'Reilly notes", "O'Reilly notes").unwrap();
+        insert_path(&conn, "/notes/O'Reilly notes", "O'Reilly notes").unwrap();
+        insert_path(&conn, "/x'); DROP TABLE item_tags; --", "evil'tag").unwrap();
+
+        assert_eq!(count(&conn, "SELECT count(*) FROM tags"), 2);
+        assert_eq!(count(&conn, "SELECT count(*) FROM item_tags"), 2);
+        let tag: String = conn
+            .query_row("SELECT tag_name FROM tags ORDER BY id LIMIT 1", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(tag, "O'Reilly notes");
+
+        let kinds = Registry::default();
+        let mut options = ShowOptions {
+            kinds: &kinds,
+            long: true,
+            columns: None,
+            by_item: false,
+            format: Format::Table,
+            nul_delimited: false,
+            tagged: TimeRange::default(),
+            sort: None,
+        };
+        let mut out = Vec::new();
+        show_tags(&conn, &mut out, &options, &[String::from("O'Reilly notes")]).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("| O'Reilly notes |"));
+        assert!(table.contains("/notes/O'Reilly notes"));
+        let mut out = Vec::new();
+        show_paths(&conn, &mut out, &options, &[String::from("O'Reilly"), String::from("100%_")]).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("/notes/O'Reilly notes"));
+
+        options.format = Format::Tsv;
+        options.columns = Some(vec![String::from("tag"), String::from("path")]);
+        let mut out = Vec::new();
+        show_paths(&conn, &mut out, &options, &[String::from("DROP")]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "TAG\tPATH\nevil'tag\t/x'); DROP TABLE item_tags; --\n");
+        assert!(show_tags(&conn, &mut Vec::new(), &options, &[String::from("rating>")]).is_err());
+
+        options.by_item = true;
+        options.sort = Some(ShowSort::TaggedAt);
+        options.tagged.since = Some(String::from("2000-01-01 00:00:00"));
+        options.columns = None;
+        let mut out = Vec::new();
+        show_paths(&conn, &mut out, &options, &[String::from("O'Reilly")]).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("O'Reilly notes"));
+
+        // each path once, although it has two tags
+        options.by_item = false;
+        options.nul_delimited = true;
+        insert_path(&conn, "/notes/O'Reilly notes", "books").unwrap();
+        let mut out = Vec::new();
+        show_paths(&conn, &mut out, &options, &[String::from("O'Reilly")]).unwrap();
+        assert_eq!(out, b"/notes/O'Reilly notes\0");
+    }
+
+    #[test]
+    fn test_delete_by_tag_with_quote() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "O'Reilly notes").unwrap();
+        insert_path(&conn, "/a", "keep").unwrap();
+        assert_eq!(delete_by_tag(&conn, &[String::from("O'Reilly notes"), String::from("missing")]).unwrap(), 1);
+
+        assert_eq!(count(&conn, "SELECT count(*) FROM tags"), 1);
+        assert_eq!(count(&conn, "SELECT count(*) FROM item_tags"), 1);
+    }
+
+    #[test]
+    fn test_get_tags_of_path() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "rust").unwrap();
+        insert_path(&conn, "/a", "rating=4").unwrap();
+        insert_path(&conn, "/b", "rust").unwrap();
+
+        assert_eq!(get_tags_of_path(&conn, "/a").unwrap(), Some(vec![String::from("rating=4"), String::from("rust")]));
+        assert_eq!(get_tags_of_path(&conn, "/b").unwrap(), Some(vec![String::from("rust")]));
+        assert_eq!(get_tags_of_path(&conn, "/c").unwrap(), None);
+    }
+
+    #[test]
+    fn test_insert_paths_is_atomic() {
+        let conn = create_new_db();
+        let tags = vec![String::from("a"), String::from("b"), String::from("c")];
+        let paths: Vec<String> = (0..100).map(|i| format!("/file{}", i)).collect();
+        insert_paths(&conn, &paths, &tags).unwrap();
+        assert_eq!(count(&conn, "SELECT count(*) FROM item_tags"), 300);
+
+        conn.execute_batch(
+            "CREATE TRIGGER fail BEFORE INSERT ON items WHEN NEW.path = '/bad'
+             BEGIN SELECT RAISE(ABORT, 'bad path'); END;",
+        )
+        .unwrap();
+        let paths = vec![String::from("/new"), String::from("/bad")];
+        assert!(insert_paths(&conn, &paths, &tags).is_err());
+        assert_eq!(count(&conn, "SELECT count(*) FROM item_tags"), 300);
+        assert_eq!(count(&conn, "SELECT count(*) FROM items WHERE path = '/new'"), 0);
+    }
+}
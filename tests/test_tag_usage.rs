@@ -0,0 +1,44 @@
+mod tag_usage_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::rtag_sqlite::{create_new_tag, get_tag_usage, initialize_tables, insert_path, TagSort};
+
+    fn names(conn: &Connection, sort: TagSort, min_count: i64, unused: bool) -> Vec<String> {
+        get_tag_usage(conn, sort, min_count, unused).unwrap().into_iter().map(|tag| tag.tag_name).collect()
+    }
+
+    #[test]
+    fn test_counts_and_filters() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "rust").unwrap();
+        insert_path(&conn, "/b", "rust").unwrap();
+        insert_path(&conn, "/a", "lang/go").unwrap();
+        create_new_tag(&conn, "empty").unwrap();
+
+        let tags = get_tag_usage(&conn, TagSort::Name, 0, false).unwrap();
+        let counts: Vec<(&str, i64)> = tags.iter().map(|tag| (tag.tag_name.as_str(), tag.count)).collect();
+        assert_eq!(counts, vec![("empty", 0), ("lang", 0), ("lang/go", 1), ("rust", 2)]);
+        assert!(tags[0].time_last_used.is_none());
+        assert!(tags[3].time_last_used.is_some());
+
+        assert_eq!(names(&conn, TagSort::Count, 1, false), vec!["rust", "lang/go"]);
+        assert_eq!(names(&conn, TagSort::Name, 2, false), vec!["rust"]);
+        assert_eq!(names(&conn, TagSort::Name, 0, true), vec!["empty", "lang"]);
+    }
+
+    #[test]
+    fn test_sort_by_last_use() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "old").unwrap();
+        insert_path(&conn, "/a", "new").unwrap();
+        conn.execute("UPDATE tags SET time_last_used = '2020-01-01 00:00:00' WHERE tag_name = 'old'", NO_PARAMS)
+            .unwrap();
+        create_new_tag(&conn, "never").unwrap();
+
+        assert_eq!(names(&conn, TagSort::Used, 0, false), vec!["new", "old", "never"]);
+        assert_eq!("used".parse::<TagSort>(), Ok(TagSort::Used));
+        assert!("size".parse::<TagSort>().is_err());
+    }
+}
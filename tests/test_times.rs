@@ -0,0 +1,77 @@
+mod times_tests {
+    use chrono::{TimeZone, Utc};
+    use rusqlite::Connection;
+
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{initialize_tables, insert_path, search_tagged};
+    use rtag::times::{parse_time, TimeRange};
+
+    #[test]
+    fn test_parse_time() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 10, 12, 30, 0).unwrap();
+        assert_eq!(parse_time("7d", now), Ok(String::from("2026-03-03 12:30:00")));
+        assert_eq!(parse_time("2w", now), Ok(String::from("2026-02-24 12:30:00")));
+        assert_eq!(parse_time("90m", now), Ok(String::from("2026-03-10 11:00:00")));
+        assert_eq!(parse_time("0s", now), Ok(String::from("2026-03-10 12:30:00")));
+        assert!(parse_time("2026-01-01", now).unwrap().starts_with("202"));
+        assert!(parse_time("2026-01-01 18:00", now).is_ok());
+        assert!(parse_time("today", now).is_ok());
+        assert!(parse_time("7y", now).is_err());
+        assert!(parse_time("-7d", now).is_err());
+        assert!(parse_time("d", now).is_err());
+        assert!(parse_time("2026-13-01", now).is_err());
+    }
+
+    #[test]
+    fn test_search_tagged() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        for (path, tag) in &[("/old", "rust"), ("/new", "rust"), ("/unknown", "rust"), ("/new", "draft")] {
+            insert_path(&conn, path, tag).unwrap();
+        }
+        conn.execute_batch(
+            "UPDATE item_tags SET tagged_at = '2026-01-05 10:00:00'
+                 WHERE item_id = (SELECT id FROM items WHERE path = '/old');
+             UPDATE item_tags SET tagged_at = '2026-03-01 10:00:00'
+                 WHERE item_id = (SELECT id FROM items WHERE path = '/new');
+             UPDATE item_tags SET tagged_at = NULL
+                 WHERE item_id = (SELECT id FROM items WHERE path = '/unknown');",
+        )
+        .unwrap();
+        let expr = parse("rust").unwrap();
+        let range = |since: Option<&str>, before: Option<&str>| TimeRange {
+            since: since.map(String::from),
+            before: before.map(String::from),
+        };
+
+        let all = search_tagged(&conn, &expr, &TimeRange::default(), true).unwrap();
+        assert_eq!(all, vec!["/new", "/old", "/unknown"]);
+        let recent = search_tagged(&conn, &expr, &range(Some("2026-02-01 00:00:00"), None), false).unwrap();
+        assert_eq!(recent, vec!["/new"]);
+        let older = search_tagged(&conn, &expr, &range(None, Some("2026-02-01 00:00:00")), false).unwrap();
+        assert_eq!(older, vec!["/old"]);
+        let between = range(Some("2026-01-05 10:00:00"), Some("2026-03-01 10:00:00"));
+        assert_eq!(search_tagged(&conn, &expr, &between, false).unwrap(), vec!["/old"]);
+    }
+
+    #[test]
+    fn test_search_tagged_limits_each_tag() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "rust").unwrap();
+        insert_path(&conn, "/a", "rating=4").unwrap();
+        conn.execute_batch(
+            "UPDATE item_tags SET tagged_at = '2026-01-05 10:00:00'
+                 WHERE tag_id = (SELECT id FROM tags WHERE tag_name = 'rust');
+             UPDATE item_tags SET tagged_at = '2026-03-01 10:00:00'
+                 WHERE tag_id = (SELECT id FROM tags WHERE tag_name = 'rating');",
+        )
+        .unwrap();
+        let recent = TimeRange { since: Some(String::from("2026-02-01 00:00:00")), before: None };
+
+        // /a was tagged within the range, but not with rust
+        assert!(search_tagged(&conn, &parse("rust").unwrap(), &recent, false).unwrap().is_empty());
+        assert_eq!(search_tagged(&conn, &parse("rating>3").unwrap(), &recent, false).unwrap(), vec!["/a"]);
+        assert_eq!(search_tagged(&conn, &parse("NOT rust").unwrap(), &recent, false).unwrap(), vec!["/a"]);
+    }
+}
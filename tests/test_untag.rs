@@ -0,0 +1,33 @@
+mod untag_tests {
+    use rusqlite::Connection;
+
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{forget_path, get_tags, initialize_tables, insert_path, search, untag_path};
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "rust").unwrap();
+        insert_path(&conn, "/b", "rust").unwrap();
+        insert_path(&conn, "/a", "paper").unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_untag_keeps_tag() {
+        let conn = create_new_db();
+        assert_eq!(untag_path(&conn, "rust", "/a").unwrap(), 1);
+        assert_eq!(untag_path(&conn, "rust", "/a").unwrap(), 0);
+        assert_eq!(untag_path(&conn, "missing", "/a").unwrap(), 0);
+        assert_eq!(search(&conn, &parse("rust").unwrap()).unwrap(), vec!["/b"]);
+        assert_eq!(search(&conn, &parse("paper").unwrap()).unwrap(), vec!["/a"]);
+    }
+
+    #[test]
+    fn test_forget_keeps_tags() {
+        let conn = create_new_db();
+        assert_eq!(forget_path(&conn, "/a").unwrap(), 2);
+        assert_eq!(search(&conn, &parse("rust OR paper").unwrap()).unwrap(), vec!["/b"]);
+        assert_eq!(get_tags(&conn).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,58 @@
+mod urls_tests {
+    use rusqlite::{Connection, NO_PARAMS};
+
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{initialize_tables, insert_items, search, URL_ITEM};
+    use rtag::urls::{is_url, normalize_url, FragmentPolicy};
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com"));
+        assert!(is_url("git+ssh://host/repo"));
+        assert!(is_url("http://[invalid"));
+        assert!(!is_url("/home/me/file"));
+        assert!(!is_url("dir/://file"));
+        assert!(!is_url("src/*.rs"));
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        let keep = FragmentPolicy::Keep;
+        assert_eq!(normalize_url("HTTPS://Example.COM:443", keep).unwrap(), "https://example.com/");
+        assert_eq!(normalize_url("http://example.com:8080/a", keep).unwrap(), "http://example.com:8080/a");
+        assert_eq!(
+            normalize_url("https://example.com/a?utm_source=x&q=rust&fbclid=1", keep).unwrap(),
+            "https://example.com/a?q=rust"
+        );
+        assert_eq!(normalize_url("https://example.com/a?utm_medium=x#", keep).unwrap(), "https://example.com/a");
+        // queries without tracking parameters are left as they are
+        assert_eq!(normalize_url("https://example.com/?q=a%20b&flag", keep).unwrap(), "https://example.com/?q=a%20b&flag");
+        // removing tracking parameters keeps the others as written
+        assert_eq!(
+            normalize_url("https://x.com/?q=a%20b&utm_source=t", keep).unwrap(),
+            normalize_url("https://x.com/?q=a%20b", keep).unwrap()
+        );
+        assert_eq!(normalize_url("https://x.com/?token&gclid=1", keep).unwrap(), "https://x.com/?token");
+        assert_eq!(normalize_url("https://x.com/?utm_%73ource=t&q=a+b", keep).unwrap(), "https://x.com/?q=a+b");
+        assert_eq!(normalize_url("https://example.com/#usage", keep).unwrap(), "https://example.com/#usage");
+        assert_eq!(
+            normalize_url("https://example.com/#usage", FragmentPolicy::Strip).unwrap(),
+            "https://example.com/"
+        );
+        assert!(normalize_url("https://[invalid", keep).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_urls_are_one_item() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        let urls: Vec<String> = ["https://EXAMPLE.com/a?utm_source=feed", "https://example.com:443/a"]
+            .iter()
+            .map(|url| normalize_url(url, FragmentPolicy::Keep).unwrap())
+            .collect();
+        insert_items(&conn, &urls, URL_ITEM, &[String::from("web")]).unwrap();
+        assert_eq!(search(&conn, &parse("web").unwrap()).unwrap(), vec!["https://example.com/a"]);
+        let item_type: String = conn.query_row("SELECT type FROM items", NO_PARAMS, |row| row.get(0)).unwrap();
+        assert_eq!(item_type, "url");
+    }
+}
@@ -0,0 +1,79 @@
+mod values_tests {
+    use chrono::{Local, NaiveDate};
+    use rusqlite::{params, Connection};
+
+    use rtag::query::{parse, CompareOp, Comparison, Expr};
+    use rtag::rtag_sqlite::{initialize_tables, insert_path, merge_tags, search};
+    use rtag::values::{split_tag_value, TagValue};
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/a", "rating=4").unwrap();
+        insert_path(&conn, "/b", "rating=10").unwrap();
+        insert_path(&conn, "/c", "rating=2.5").unwrap();
+        insert_path(&conn, "/d", "rating=high").unwrap();
+        insert_path(&conn, "/a", "due=2020-01-01").unwrap();
+        insert_path(&conn, "/b", "due=2999-12-31").unwrap();
+        insert_path(&conn, "/c", "status=reading").unwrap();
+        conn
+    }
+
+    fn search_str(conn: &Connection, query: &str) -> Vec<String> {
+        search(conn, &parse(query).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_value_types() {
+        assert_eq!(TagValue::parse("4"), TagValue::Int(4));
+        assert_eq!(TagValue::parse("-2.5"), TagValue::Float(-2.5));
+        assert_eq!(TagValue::parse("2026-11-01"), TagValue::Date(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap()));
+        assert_eq!(TagValue::parse("today"), TagValue::Date(Local::now().date_naive()));
+        assert_eq!(TagValue::parse("2026-13-01"), TagValue::Text(String::from("2026-13-01")));
+        assert_eq!(TagValue::parse("NaN"), TagValue::Text(String::from("NaN")));
+        assert_eq!(split_tag_value("status=to=do"), ("status", Some(TagValue::Text(String::from("to=do")))));
+        assert_eq!(split_tag_value("rust"), ("rust", None));
+    }
+
+    #[test]
+    fn test_parse_comparisons() {
+        let comparison = Comparison { tag: String::from("rating"), op: CompareOp::Ge, value: TagValue::Int(4) };
+        assert_eq!(parse("rating>=4").unwrap(), Expr::Compare(comparison));
+        assert_eq!(parse("\"a=b\"").unwrap(), Expr::Tag(String::from("a=b")));
+        assert!(parse("rating>=").is_err());
+        assert!(parse(">4").is_err());
+    }
+
+    #[test]
+    fn test_search_compares_by_type() {
+        let conn = create_new_db();
+        // 10 > 4 numerically, not as text, and the string value is skipped
+        assert_eq!(search_str(&conn, "rating>=4"), vec!["/a", "/b"]);
+        assert_eq!(search_str(&conn, "rating<4"), vec!["/c"]);
+        assert_eq!(search_str(&conn, "rating=2.5 OR rating=high"), vec!["/c", "/d"]);
+        assert_eq!(search_str(&conn, "rating!=4"), vec!["/b", "/c"]);
+        assert_eq!(search_str(&conn, "due<today"), vec!["/a"]);
+        assert_eq!(search_str(&conn, "rating AND NOT due>=today"), vec!["/a", "/c", "/d"]);
+        assert_eq!(search_str(&conn, "status=reading AND rating>2"), vec!["/c"]);
+    }
+
+    #[test]
+    fn test_values_are_updated_and_merged() {
+        let conn = create_new_db();
+        insert_path(&conn, "/a", "rating=1").unwrap();
+        insert_path(&conn, "/a", "rating").unwrap();
+        assert_eq!(search_str(&conn, "rating<2"), vec!["/a"]);
+
+        merge_tags(&conn, &[String::from("rating")], "score").unwrap();
+        assert_eq!(search_str(&conn, "score>=4"), vec!["/b"]);
+        assert_eq!(search_str(&conn, "score=2.5"), vec!["/c"]);
+        let value_type: String = conn
+            .query_row(
+                "SELECT value_type FROM item_tags JOIN tags ON tags.id = tag_id WHERE tag_name = ?1 AND value = ?2",
+                params!["score", 2.5],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value_type, "float");
+    }
+}
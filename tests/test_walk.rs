@@ -0,0 +1,48 @@
+mod walk_tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use rtag::walk::{walk_dir, EntryType, WalkOptions};
+
+    fn relative(root: &Path, paths: Vec<PathBuf>) -> Vec<String> {
+        paths.iter().map(|path| path.strip_prefix(root).unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    fn create_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for dir_name in &["src/deep", ".hidden", ".git", ".rtag", "target"] {
+            fs::create_dir_all(dir.path().join(dir_name)).unwrap();
+        }
+        for file in &["a.txt", "src/b.rs", "src/deep/c.rs", "src/skip.log", ".hidden/d", ".env", ".git/HEAD", ".rtag/rtag.db", "target/e"] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        fs::write(dir.path().join("src/.rtagignore"), "*.log\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_walk_respects_ignore_files() {
+        let dir = create_tree();
+        let paths = walk_dir(dir.path(), &WalkOptions::default());
+        assert_eq!(relative(dir.path(), paths), vec!["a.txt", "src/b.rs", "src/deep/c.rs"]);
+
+        let options = WalkOptions { hidden: true, ..WalkOptions::default() };
+        let paths = walk_dir(dir.path(), &options);
+        // .git and .rtag are never entered, even with hidden files
+        assert_eq!(
+            relative(dir.path(), paths),
+            vec![".env", ".gitignore", ".hidden/d", "a.txt", "src/.rtagignore", "src/b.rs", "src/deep/c.rs"]
+        );
+    }
+
+    #[test]
+    fn test_walk_depth_and_type() {
+        let dir = create_tree();
+        let options = WalkOptions { max_depth: Some(1), ..WalkOptions::default() };
+        assert_eq!(relative(dir.path(), walk_dir(dir.path(), &options)), vec!["a.txt"]);
+
+        let options = WalkOptions { entry_type: EntryType::Dir, ..WalkOptions::default() };
+        assert_eq!(relative(dir.path(), walk_dir(dir.path(), &options)), vec!["src", "src/deep"]);
+    }
+}
@@ -0,0 +1,126 @@
+mod watch_tests {
+    use notify::event::{CreateKind, EventKind, ModifyKind, RemoveKind, RenameMode};
+    use notify::Event;
+    use rusqlite::{Connection, NO_PARAMS};
+    use std::path::PathBuf;
+
+    use rtag::location::DbLocation;
+    use rtag::query::parse;
+    use rtag::rtag_sqlite::{initialize_tables, insert_path, mark_missing, mark_present, move_path, search};
+    use rtag::watch::{apply_change, changes_of, default_roots, Change};
+
+    fn create_new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/d", "project").unwrap();
+        insert_path(&conn, "/d/a", "rust").unwrap();
+        insert_path(&conn, "/d/sub/b", "rust").unwrap();
+        insert_path(&conn, "/d2/c", "rust").unwrap();
+        conn
+    }
+
+    fn missing(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT path FROM items WHERE missing_since IS NOT NULL ORDER BY path").unwrap();
+        let paths = stmt.query_map(NO_PARAMS, |row| row.get(0)).unwrap();
+        paths.collect::<rusqlite::Result<Vec<String>>>().unwrap()
+    }
+
+    #[test]
+    fn test_move_directory() {
+        let conn = create_new_db();
+        assert_eq!(move_path(&conn, "/d", "/e").unwrap(), 3);
+        assert_eq!(search(&conn, &parse("rust").unwrap()).unwrap(), vec!["/d2/c", "/e/a", "/e/sub/b"]);
+        assert_eq!(search(&conn, &parse("project").unwrap()).unwrap(), vec!["/e"]);
+        // moving onto a tagged path merges the tags
+        assert_eq!(move_path(&conn, "/e", "/d2/c").unwrap(), 3);
+        assert_eq!(search(&conn, &parse("rust AND project").unwrap()).unwrap(), vec!["/d2/c"]);
+        assert_eq!(move_path(&conn, "/missing", "/x").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_directories_differing_in_case() {
+        let conn = create_new_db();
+        insert_path(&conn, "/d/Foo/a", "case").unwrap();
+        insert_path(&conn, "/d/foo/b", "case").unwrap();
+        assert_eq!(move_path(&conn, "/d/Foo", "/d/Bar").unwrap(), 1);
+        assert_eq!(search(&conn, &parse("case").unwrap()).unwrap(), vec!["/d/Bar/a", "/d/foo/b"]);
+        assert_eq!(mark_missing(&conn, "/d/BAR").unwrap(), 0);
+        assert_eq!(mark_missing(&conn, "/d/Bar").unwrap(), 1);
+        assert_eq!(missing(&conn), vec!["/d/Bar/a"]);
+    }
+
+    #[test]
+    fn test_missing_items_keep_their_tags() {
+        let conn = create_new_db();
+        assert_eq!(mark_missing(&conn, "/d/sub").unwrap(), 1);
+        assert_eq!(mark_missing(&conn, "/d").unwrap(), 2);
+        assert_eq!(missing(&conn), vec!["/d", "/d/a", "/d/sub/b"]);
+        assert_eq!(search(&conn, &parse("rust").unwrap()).unwrap().len(), 3);
+
+        assert_eq!(mark_present(&conn, "/d/a").unwrap(), 1);
+        assert_eq!(move_path(&conn, "/d", "/e").unwrap(), 3);
+        assert!(missing(&conn).is_empty());
+    }
+
+    #[test]
+    fn test_apply_events() {
+        let conn = create_new_db();
+        let db = DbLocation { path: PathBuf::from("/rtag.db"), root: None };
+        let rename = |mode| Event::new(EventKind::Modify(ModifyKind::Name(mode)));
+        let events = [
+            rename(RenameMode::From).add_path(PathBuf::from("/d/a")),
+            rename(RenameMode::To).add_path(PathBuf::from("/d/a2")),
+            rename(RenameMode::Both).add_path(PathBuf::from("/d/a")).add_path(PathBuf::from("/d/a2")),
+            Event::new(EventKind::Remove(RemoveKind::File)).add_path(PathBuf::from("/d2/c")),
+            Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/d/sub/b")),
+        ];
+        let changes: Vec<Change> = events.iter().flat_map(changes_of).collect();
+        assert_eq!(changes[2], Change::Moved(PathBuf::from("/d/a"), PathBuf::from("/d/a2")));
+        for change in &changes {
+            apply_change(&conn, &db, change).unwrap();
+        }
+        assert_eq!(search(&conn, &parse("rust").unwrap()).unwrap(), vec!["/d/a2", "/d/sub/b", "/d2/c"]);
+        assert_eq!(missing(&conn), vec!["/d2/c"]);
+    }
+
+    #[test]
+    fn test_store_files_are_ignored() {
+        let conn = create_new_db();
+        insert_path(&conn, ".rtag", "store").unwrap();
+        insert_path(&conn, "/rtag.db-journal", "store").unwrap();
+        let local = DbLocation { path: PathBuf::from("/p/.rtag/rtag.db"), root: Some(PathBuf::from("/p")) };
+        for path in &["/p/.rtag", "/p/.rtag/rtag.db-journal"] {
+            assert_eq!(apply_change(&conn, &local, &Change::Removed(PathBuf::from(path))).unwrap(), 0);
+        }
+        let global = DbLocation { path: PathBuf::from("/rtag.db"), root: None };
+        let journal = Change::Removed(PathBuf::from("/rtag.db-journal"));
+        assert_eq!(apply_change(&conn, &global, &journal).unwrap(), 0);
+        assert!(missing(&conn).is_empty());
+        assert_eq!(apply_change(&conn, &global, &Change::Removed(PathBuf::from("/d/a"))).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_default_roots() {
+        let paths: Vec<PathBuf> = ["/a/b/c", "/a/x", "/a/b/d/e", "/f/g"].iter().map(PathBuf::from).collect();
+        assert_eq!(default_roots(&paths), vec![PathBuf::from("/a"), PathBuf::from("/f")]);
+    }
+
+    #[test]
+    fn test_changes_without_effect_write_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rtag.db");
+        let conn = Connection::open(&path).unwrap();
+        initialize_tables(&conn).unwrap();
+        insert_path(&conn, "/d/a", "rust").unwrap();
+        mark_missing(&conn, "/d/a").unwrap();
+        drop(conn);
+
+        let conn = Connection::open(&path).unwrap();
+        assert_eq!(mark_present(&conn, "/d/other").unwrap(), 0);
+        assert_eq!(mark_missing(&conn, "/d/a").unwrap(), 0);
+        assert_eq!(mark_missing(&conn, "/d/other").unwrap(), 0);
+        assert_eq!(move_path(&conn, "/d/other", "/d/moved").unwrap(), 0);
+        let total_changes: i64 = conn.query_row("SELECT total_changes()", NO_PARAMS, |row| row.get(0)).unwrap();
+        assert_eq!(total_changes, 0);
+    }
+}